@@ -1,11 +1,14 @@
 use std::ffi::OsStr;
 use std::fs::OpenOptions;
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader, Write};
-use std::os::unix::net::{UnixListener, UnixStream};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::net::{UnixDatagram, UnixListener, UnixStream};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::str::FromStr;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
@@ -36,9 +39,24 @@ use std::{io, path};
  *      - pm-daemon.err         - daemon stderr
  *      - <appname>.log         - app stdout&stderr
  *      - /tmp/pm.sock          - ipc socket between daemon and cli
+ *      - /tmp/pm.state         - pids of running apps, see `StateFile`
  */
 
 const SOCKET_PATH: &str = "/tmp/pm.sock";
+/* commands whose first param selects apps, see `select_apps` */
+const SELECTOR_COMMANDS: &[&str] = &[
+    "start", "stop", "restart", "enable", "disable", "remove", "reload", "reload-config",
+    "run", "scale", "sched", "signal", "pause", "resume", "send", "attach", "watch", "edit",
+    "show", "history",
+];
+/* running apps, for a restarted daemon to adopt them, see `StateFile` */
+const STATE_PATH: &str = "/tmp/pm.state";
+/* locked by the running daemon */
+const PID_PATH: &str = "/tmp/pm-daemon.pid";
+/* exits of apps, see `ExitHistory` */
+const HISTORY_PATH: &str = "/tmp/pm.history";
+/* exits kept per app */
+const EXIT_HISTORY_LEN: usize = 100;
 
 use serde::{Deserialize, Serialize};
 
@@ -46,697 +64,5571 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug)]
 struct ProcessChild {
     name: String,
-    child: Child,
+    /* 0 ~ instances-1, passed to the app as PM_INSTANCE_ID */
+    instance: u32,
+    child: AppProcess,
+    started_at: time::SystemTime,
+    /* with `tty`, the daemon side of its pseudo terminal */
+    pty: Option<Arc<Pty>>,
+    /* set by `pause`, how to resume it */
+    paused: Option<Freezer>,
+    /* set when pm kills it on its own, e.g. for missing watchdog, for `history` */
+    killed_for: Option<ExitTrigger>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AppConfig {
-    /**
-     * name act as a unique identifier of the app,
-     * if create a app with same name, old one will be overwriten
-     * app specific operation base on name,
-     * e.g. enable name1, disable name2
-     * If not provided, name will be same as path
-     */
-    pub name: String,
-    pub cmd: String,
-    pub args: Vec<String>,
-    pub cwd: path::PathBuf,
-    pub enabled: bool,
-    pub logdir: Option<path::PathBuf>,
-    pub kill_signal: Option<String>,
-    #[serde(skip)]
-    exit_count: i32,
+/**
+ * a process spawned by this daemon, or one adopted from an earlier daemon,
+ * after `daemon upgrade` it's still our child and is waited as usual,
+ * after a crash it's not, then it's checked through /proc,
+ * and its exit status is unknown (reported as 0, and kept as unknown in history)
+ */
+#[derive(Debug)]
+enum AppProcess {
+    Spawned(Child),
+    Adopted {
+        pid: u32,
+        start_time: u64,
+        stdin: Option<std::process::ChildStdin>,
+        /* exit seen through /proc, not by waitpid */
+        exit_unknown: bool,
+    },
+}
+
+impl AppProcess {
+    fn id(&self) -> u32 {
+        match self {
+            AppProcess::Spawned(child) => child.id(),
+            AppProcess::Adopted { pid, .. } => *pid,
+        }
+    }
+
+    fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        match self {
+            AppProcess::Spawned(child) => child.try_wait(),
+            /* gone, zombie, or pid reused by another process */
+            AppProcess::Adopted {
+                pid,
+                start_time,
+                exit_unknown,
+                ..
+            } => {
+                let mut status = 0;
+                match unsafe { libc::waitpid(*pid as i32, &mut status, libc::WNOHANG) } {
+                    0 => return Ok(None),
+                    ret if ret > 0 => return Ok(Some(ExitStatus::from_raw(status))),
+                    _ => {}
+                }
+                /* ECHILD, not our child */
+                match proc_stat(*pid) {
+                    Some((state, time)) if state != 'Z' && time == *start_time => Ok(None),
+                    _ => {
+                        *exit_unknown = true;
+                        Ok(Some(ExitStatus::from_raw(0)))
+                    }
+                }
+            }
+        }
+    }
+
+    /* `exit_status` from `try_wait`, None if it's made up */
+    fn known_exit_status(&self, exit_status: ExitStatus) -> Option<ExitStatus> {
+        match self {
+            AppProcess::Adopted { exit_unknown: true, .. } => None,
+            _ => Some(exit_status),
+        }
+    }
+
+    fn kill(&mut self) -> std::io::Result<()> {
+        match self {
+            AppProcess::Spawned(child) => child.kill(),
+            AppProcess::Adopted { pid, .. } => {
+                let pid = nix::unistd::Pid::from_raw(*pid as i32);
+                if let Ok(None) = self.try_wait() {
+                    nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL)?;
+                    return Ok(());
+                }
+                Err(io::Error::new(io::ErrorKind::InvalidInput, "process has exited"))
+            }
+        }
+    }
+
+    fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        match self {
+            AppProcess::Spawned(child) => child.wait(),
+            AppProcess::Adopted { .. } => loop {
+                if let Some(exit_status) = self.try_wait()? {
+                    return Ok(exit_status);
+                }
+                thread::sleep(Duration::from_millis(50));
+            },
+        }
+    }
+
+    fn stdin(&mut self) -> Option<&mut std::process::ChildStdin> {
+        match self {
+            AppProcess::Spawned(child) => child.stdin.as_mut(),
+            AppProcess::Adopted { stdin, .. } => stdin.as_mut(),
+        }
+    }
 }
 
+/**
+ * what /tmp/pm.state keeps for each running instance,
+ * start time and cmdline tell if the pid still belongs to the app
+ */
+#[derive(Debug, Serialize, Deserialize)]
+struct StateEntry {
+    name: String,
+    instance: u32,
+    pid: u32,
+    pgid: u32,
+    /* field 22 of /proc/<pid>/stat, clock ticks since boot */
+    start_time: u64,
+    cmdline: Vec<String>,
+    /* seconds since epoch, for `ls` and run records */
+    started_at: u64,
+    /* below are only written by `daemon upgrade`, fds survive exec */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stdin_fd: Option<RawFd>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pty_fd: Option<RawFd>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    paused: Option<Freezer>,
+}
 
+/**
+ * runtime state of an app kept across `daemon upgrade`
+ */
 #[derive(Debug, Serialize, Deserialize)]
-pub struct Config {
-    /* this allows .toml to be empty */
+struct AppRuntimeState {
+    name: String,
+    exit_count: i32,
+    run_queued: bool,
     #[serde(default)]
-    pub apps: Vec<AppConfig>,
-    #[serde(skip)]
-    config_filepath: path::PathBuf,
+    stopped: bool,
+    listen_fds: Vec<RawFd>,
+    /* (started_at secs, duration ms, raw wait status) */
+    last_run: Option<(u64, u64, i32)>,
+    #[serde(default)]
+    restarts: BTreeMap<String, u32>,
+    #[serde(default)]
+    watch_off: bool,
+}
+
+/**
+ * why an app exited
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ExitTrigger {
+    /* a service exited by itself */
+    Crash,
+    /* a oneshot/cron run is done */
+    Finished,
+    /* stopped by pm on request, `stop`, `restart`, `scale` down... */
+    Stop,
+    /* replaced by a new instance in `reload` */
+    Reload,
+    /* restarted by `cron_restart` */
+    CronRestart,
+    /* restarted as a `watch` path changed */
+    Watch,
+    /* killed for missing `watchdog_sec` */
+    HealthCheck,
+    /* SIGKILLed by the OOM killer, for the memory limit of its cgroup or system wide */
+    MemoryLimit,
+    /* SIGKILLed, but its cgroup's memory.events can't tell if by the OOM killer */
+    Unknown,
 }
 
+impl std::fmt::Display for ExitTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ExitTrigger::Crash => "crash",
+            ExitTrigger::Finished => "finished",
+            ExitTrigger::Stop => "user stop",
+            ExitTrigger::Reload => "reload",
+            ExitTrigger::CronRestart => "cron restart",
+            ExitTrigger::Watch => "file change",
+            ExitTrigger::HealthCheck => "health check",
+            ExitTrigger::MemoryLimit => "memory limit",
+            ExitTrigger::Unknown => "unknown",
+        };
+        write!(f, "{s}")
+    }
+}
 
-impl Config {
-    pub fn load(&mut self) -> std::io::Result<()> {
-        match fs::read_to_string(&self.config_filepath) {
-            Ok(content) => {
-                let config: Self = toml::from_str(&content)
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-                // Ok(config)
-                self.apps = config.apps;
-                Ok(())
-            }
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
-                // File missing: use default
-                // Ok(self.default())
-                self.apps = self.default().apps;
-                Ok(())
-            }
-            Err(err) => Err(err), // Propagate other errors
+/**
+ * one exit of an app instance
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExitRecord {
+    instance: u32,
+    pid: u32,
+    /* secs since epoch */
+    time: u64,
+    /* raw wait status, None for an adopted process which is not our child */
+    status: Option<i32>,
+    uptime_secs: u64,
+    trigger: ExitTrigger,
+}
+
+impl ExitRecord {
+    fn new(process_child: &ProcessChild, exit_status: ExitStatus, trigger: ExitTrigger) -> Self {
+        let now = time::SystemTime::now();
+        ExitRecord {
+            instance: process_child.instance,
+            pid: process_child.child.id(),
+            time: now.duration_since(time::UNIX_EPOCH).unwrap_or_default().as_secs(),
+            status: process_child
+                .child
+                .known_exit_status(exit_status)
+                .map(ExitStatus::into_raw),
+            uptime_secs: now
+                .duration_since(process_child.started_at)
+                .unwrap_or_default()
+                .as_secs(),
+            trigger,
         }
+    }
 
-        // let content = fs::read_to_string(&self.config_filepath)?;
-        // let config = toml::from_str(&content)
-        //     .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        // Ok(config)
+    fn time(&self) -> time::SystemTime {
+        time::UNIX_EPOCH + Duration::from_secs(self.time)
     }
 
-    pub fn save(&self) -> std::io::Result<()> {
-        let content = toml::to_string_pretty(self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    fn exit_status(&self) -> Option<ExitStatus> {
+        self.status.map(ExitStatus::from_raw)
+    }
+}
 
-        OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.config_filepath)
-            .unwrap()
-            .write_all(&content.into_bytes())
-        // fs::write(&self.config_filepath, content)
+/**
+ * last EXIT_HISTORY_LEN exits of each app, for `history` and `show`,
+ * written to HISTORY_PATH on every exit, so it survives daemon restarts
+ */
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ExitHistory {
+    #[serde(default)]
+    apps: BTreeMap<String, VecDeque<ExitRecord>>,
+}
+
+impl ExitHistory {
+    fn load() -> Self {
+        let Ok(content) = fs::read_to_string(HISTORY_PATH) else {
+            return Self::default();
+        };
+        toml::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("[pm][Warn] ignore broken {HISTORY_PATH}: {e}");
+            Self::default()
+        })
     }
-    pub fn add_config(&mut self, new_config: AppConfig) {
-        if let Some(old_config) = self.find_config(&new_config.name) {
-            *old_config = new_config;
-        } else {
-            self.apps.push(new_config);
+
+    fn record(&mut self, app_name: &str, record: ExitRecord) {
+        let exits = self.apps.entry(app_name.to_string()).or_default();
+        exits.push_back(record);
+        while exits.len() > EXIT_HISTORY_LEN {
+            exits.pop_front();
         }
-        self.save()
-            .unwrap_or_else(|_| eprintln!("[pm][Error] save config failed"));
-        // self.save().expect("???");
-        // eprintln!("[pm][Error] the App already exists")
+        self.save();
     }
-    pub fn enable(&mut self, name: &str, enabled: bool) {
-        if let Some(appconfig) = self.find_config(name) {
-            appconfig.enabled = enabled;
+
+    /* for a removed app */
+    fn forget(&mut self, app_name: &str) {
+        if self.apps.remove(app_name).is_some() {
+            self.save();
         }
-        self.save()
-            .unwrap_or_else(|_| eprintln!("[pm][Error] save config failed"));
     }
 
+    fn last(&self, app_name: &str) -> Option<&ExitRecord> {
+        self.apps.get(app_name)?.back()
+    }
 
-    pub fn find_config(&mut self, name: &str) -> std::option::Option<&mut AppConfig> {
-        self.apps.iter_mut().find(|i| i.name == name)
+    fn save(&self) {
+        /* write then rename, same as StateFile */
+        let tmp_path = format!("{HISTORY_PATH}.tmp");
+        let result = toml::to_string(self)
+            .map_err(io::Error::other)
+            .and_then(|content| fs::write(&tmp_path, content))
+            .and_then(|()| fs::rename(&tmp_path, HISTORY_PATH));
+        if let Err(e) = result {
+            eprintln!("[pm][Warn] failed to save {HISTORY_PATH}: {e}");
+        }
     }
+}
+
+/**
+ * written whenever processes_table changes, so if daemon crashes
+ * or is restarted, the new one adopts running apps instead of spawning duplicates
+ */
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StateFile {
+    #[serde(default)]
+    processes: Vec<StateEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    apps: Vec<AppRuntimeState>,
+}
 
+impl StateFile {
+    fn save(processes_table: &[ProcessChild]) {
+        if let Err(e) = Self::build(processes_table).write() {
+            eprintln!("[pm][Warn] failed to save {STATE_PATH}: {e}");
+        }
+    }
 
-    fn default(&self) -> Self {
-        Self {
+    fn build(processes_table: &[ProcessChild]) -> Self {
+        StateFile {
+            processes: processes_table
+                .iter()
+                .filter_map(|process_child| {
+                    let pid = process_child.child.id();
+                    let (_, start_time) = proc_stat(pid)?;
+                    Some(StateEntry {
+                        name: process_child.name.clone(),
+                        instance: process_child.instance,
+                        pid,
+                        pgid: nix::unistd::getpgid(Some(nix::unistd::Pid::from_raw(pid as i32)))
+                            .map(|pgid| pgid.as_raw() as u32)
+                            .unwrap_or(pid),
+                        start_time,
+                        cmdline: proc_cmdline(pid)?,
+                        started_at: process_child
+                            .started_at
+                            .duration_since(time::UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs(),
+                        stdin_fd: None,
+                        pty_fd: None,
+                        paused: None,
+                    })
+                })
+                .collect(),
             apps: vec![],
-            config_filepath: self.config_filepath.clone(),
         }
     }
-}
 
-struct ProcessManagerDaemon {
-    config: Arc<Mutex<Config>>,
-    processes_table: Arc<Mutex<Vec<ProcessChild>>>,
+    fn write(&self) -> std::io::Result<()> {
+        /* write then rename, a crash in between leaves the old file */
+        let tmp_path = format!("{STATE_PATH}.tmp");
+        let content = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, STATE_PATH)
+    }
+
+    /**
+     * for `daemon upgrade`, also keep fds of pipes, ptys and sockets, and counters,
+     * their CLOEXEC is cleared, so they are still open after exec
+     */
+    fn build_for_upgrade(config: &Config, processes_table: &[ProcessChild]) -> Self {
+        let mut state = Self::build(processes_table);
+        for entry in state.processes.iter_mut() {
+            let Some(process_child) = processes_table
+                .iter()
+                .find(|p| p.name == entry.name && p.instance == entry.instance)
+            else {
+                continue;
+            };
+            let stdin_fd = match &process_child.child {
+                AppProcess::Spawned(child) => child.stdin.as_ref().map(|stdin| stdin.as_raw_fd()),
+                AppProcess::Adopted { stdin, .. } => stdin.as_ref().map(|stdin| stdin.as_raw_fd()),
+            };
+            entry.stdin_fd = stdin_fd.filter(|fd| set_cloexec(*fd, false).is_ok());
+            entry.pty_fd = process_child
+                .pty
+                .as_ref()
+                .map(|pty| pty.master.as_raw_fd())
+                .filter(|fd| set_cloexec(*fd, false).is_ok());
+            entry.paused = process_child.paused.clone();
+        }
+        state.apps = config
+            .apps
+            .iter()
+            .map(|app_config| AppRuntimeState {
+                name: app_config.name.clone(),
+                exit_count: app_config.exit_count,
+                run_queued: app_config.run_queued,
+                stopped: app_config.stopped,
+                listen_fds: app_config
+                    .listen_fds
+                    .iter()
+                    .map(|fd| fd.as_raw_fd())
+                    .filter(|fd| set_cloexec(*fd, false).is_ok())
+                    .collect(),
+                last_run: app_config.last_run.as_ref().map(|record| {
+                    (
+                        record
+                            .started_at
+                            .duration_since(time::UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs(),
+                        record.duration.as_millis() as u64,
+                        record.exit_status.into_raw(),
+                    )
+                }),
+                restarts: app_config.restarts.clone(),
+                watch_off: app_config.watch_off,
+            })
+            .collect();
+        state
+    }
+
+    /**
+     * instances of configured apps which are still alive and still the same process,
+     * with `upgraded` (exec by `daemon upgrade`), fds and counters are taken back too
+     */
+    fn adopt(config: &mut Config, upgraded: bool) -> Vec<ProcessChild> {
+        let Ok(content) = fs::read_to_string(STATE_PATH) else {
+            return vec![];
+        };
+        let state: StateFile = toml::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("[pm][Warn] ignore broken {STATE_PATH}: {e}");
+            StateFile::default()
+        });
+        if upgraded {
+            for app_state in state.apps {
+                let Some(app_config) = config.find_config(&app_state.name) else {
+                    continue;
+                };
+                app_config.exit_count = app_state.exit_count;
+                app_config.run_queued = app_state.run_queued;
+                app_config.stopped = app_state.stopped;
+                app_config.restarts = app_state.restarts;
+                app_config.watch_off = app_state.watch_off;
+                app_config.listen_fds = app_state
+                    .listen_fds
+                    .into_iter()
+                    .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+                    .inspect(|fd| {
+                        let _ = set_cloexec(fd.as_raw_fd(), true);
+                    })
+                    .collect();
+                app_config.last_run =
+                    app_state
+                        .last_run
+                        .map(|(started_at, duration, exit_status)| RunRecord {
+                            started_at: time::UNIX_EPOCH + Duration::from_secs(started_at),
+                            duration: Duration::from_millis(duration),
+                            exit_status: ExitStatus::from_raw(exit_status),
+                        });
+            }
+        }
+        let mut adopted: Vec<ProcessChild> = vec![];
+        for entry in state.processes {
+            /* take fds first, so they are closed if not adopted */
+            let take_fd = |fd: Option<RawFd>| {
+                fd.filter(|_| upgraded).map(|fd| {
+                    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+                    let _ = set_cloexec(fd.as_raw_fd(), true);
+                    fd
+                })
+            };
+            let stdin_fd = take_fd(entry.stdin_fd);
+            let pty_fd = take_fd(entry.pty_fd);
+            let alive = matches!(proc_stat(entry.pid), Some((state, start_time))
+                if state != 'Z' && start_time == entry.start_time);
+            let same = alive && proc_cmdline(entry.pid).as_ref() == Some(&entry.cmdline);
+            let configured = config.apps.iter().any(|app| app.name == entry.name);
+            let duplicated = adopted
+                .iter()
+                .any(|p| p.name == entry.name && p.instance == entry.instance);
+            if !same || !configured || duplicated {
+                println!(
+                    "[pm][Info] not adopting {} #{} (pid {}): {}",
+                    entry.name,
+                    entry.instance,
+                    entry.pid,
+                    match (same, configured) {
+                        (false, _) => "exited or pid reused",
+                        (_, false) => "no longer in config",
+                        _ => "duplicated",
+                    }
+                );
+                continue;
+            }
+            println!(
+                "[pm][Info] adopted {} #{} (pid {}, pgid {})",
+                entry.name, entry.instance, entry.pid, entry.pgid
+            );
+            let pty = pty_fd.map(|master| {
+                let pty = Arc::new(Pty {
+                    master: File::from(master),
+                    attached: Mutex::new(None),
+                    scrollback: Mutex::new(vec![]),
+                });
+                /* append, log of this run is already there */
+                let log = config.apps.iter().find(|app| app.name == entry.name).and_then(|app| {
+                    OpenOptions::new()
+                        .append(true)
+                        .create(true)
+                        .open(app.log_file(entry.instance))
+                        .ok()
+                });
+                if let Some(log) = log {
+                    pty.start_reader(log);
+                }
+                pty
+            });
+            adopted.push(ProcessChild {
+                name: entry.name,
+                instance: entry.instance,
+                child: AppProcess::Adopted {
+                    pid: entry.pid,
+                    start_time: entry.start_time,
+                    stdin: stdin_fd.map(std::process::ChildStdin::from),
+                    exit_unknown: false,
+                },
+                started_at: time::UNIX_EPOCH + Duration::from_secs(entry.started_at),
+                pty,
+                paused: entry.paused,
+                killed_for: None,
+            });
+        }
+        adopted
+    }
 }
 
-const DEFAULT_LOG_DIR: &str = "/tmp";
+/**
+ * how a paused app was frozen,
+ * cgroup v2 freezer if the app's cgroup holds only the app (it also catches
+ * forks and is not visible to the app), SIGSTOP to its process group otherwise
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Freezer {
+    Cgroup(PathBuf),
+    ProcessGroup(i32),
+}
 
-impl ProcessManagerDaemon {
-    pub fn new(config_filepath: &path::Path) -> ProcessManagerDaemon {
-        let new_pmd = Self {
-            config: Arc::new(Mutex::new(Config {
-                apps: vec![],
-                config_filepath: config_filepath.to_path_buf(),
-            })),
-            processes_table: Arc::new(Mutex::new(Vec::<ProcessChild>::new())),
+impl Freezer {
+    fn freeze(pid: u32) -> std::io::Result<Self> {
+        /*
+         * pm doesn't create cgroups, so only freeze one holding nothing but
+         * the app and its forks, anything else in it would be frozen too
+         */
+        let only_app = |dir: &Path| {
+            fs::read_to_string(dir.join("cgroup.procs")).is_ok_and(|procs| {
+                procs.lines().all(|proc| {
+                    proc.parse::<u32>().is_ok_and(|proc| {
+                        proc == pid
+                            || nix::unistd::getpgid(Some(nix::unistd::Pid::from_raw(proc as i32)))
+                                .is_ok_and(|pgid| pgid.as_raw() == pid as i32)
+                    })
+                })
+            })
         };
+        /* cgroup shared with daemon would freeze daemon too */
+        if let (Some(dir), Some(daemon_dir)) = (proc_cgroup_dir(&pid.to_string()), proc_cgroup_dir("self"))
+            && dir != daemon_dir
+            && only_app(&dir)
+            && fs::write(dir.join("cgroup.freeze"), "1").is_ok()
+        {
+            return Ok(Freezer::Cgroup(dir));
+        }
+        /* every app is its own process group leader, see `spawn_process` */
+        let pgid = nix::unistd::getpgid(Some(nix::unistd::Pid::from_raw(pid as i32)))?;
+        if pgid == nix::unistd::getpgrp() {
+            return Err(io::Error::other("app shares process group with daemon"));
+        }
+        nix::sys::signal::killpg(pgid, nix::sys::signal::Signal::SIGSTOP)?;
+        Ok(Freezer::ProcessGroup(pgid.as_raw()))
+    }
 
-        new_pmd
-            .config
-            .lock()
-            .unwrap()
-            .load()
-            .expect("why load config failed?");
+    fn thaw(&self) -> std::io::Result<()> {
+        match self {
+            Freezer::Cgroup(dir) => fs::write(dir.join("cgroup.freeze"), "0"),
+            Freezer::ProcessGroup(pgid) => Ok(nix::sys::signal::killpg(
+                nix::unistd::Pid::from_raw(*pgid),
+                nix::sys::signal::Signal::SIGCONT,
+            )?),
+        }
+    }
 
+    fn describe(&self) -> String {
+        match self {
+            Freezer::Cgroup(dir) => format!("cgroup {}", dir.display()),
+            Freezer::ProcessGroup(pgid) => format!("SIGSTOP to process group {pgid}"),
+        }
+    }
+}
 
-        new_pmd.start_all_apps();
-        new_pmd.start_watchdog_loop();
+impl ProcessChild {
+    /**
+     * a stopped process can't handle kill_signal, so resume before stopping it
+     */
+    fn resume_if_paused(&mut self) {
+        if let Some(freezer) = self.paused.take()
+            && let Err(e) = freezer.thaw()
+        {
+            eprintln!("[pm][Warn] failed to resume {}: {e}", self.name);
+        }
+    }
 
-        new_pmd
+    /**
+     * exit status once it exited, a paused one can exit too (e.g. SIGKILL),
+     * then it's no longer paused, and forks left in its group or cgroup are thawed
+     */
+    fn try_exit(&mut self) -> Option<ExitStatus> {
+        let exit_status = self.child.try_wait().ok()??;
+        if let Some(freezer) = self.paused.take() {
+            /* nothing may be left to thaw */
+            let _ = freezer.thaw();
+        }
+        Some(exit_status)
     }
-    pub fn start_listening(self: &Self) -> std::io::Result<()> {
-        // Remove previous socket file if it exists
-        let _ = fs::remove_file(SOCKET_PATH);
-        let listener = UnixListener::bind(SOCKET_PATH)?;
-        println!("Daemon listening on {}", SOCKET_PATH);
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    let cloned_config = self.config.clone();
-                    let cloned_processes_table = self.processes_table.clone();
-                    let handler = Self {
-                        config: cloned_config,
-                        processes_table: cloned_processes_table,
-                    };
-                    /* FIXME\: thread and struct...? */
-                    thread::spawn(move || handler.handle_client(stream));
+}
+
+/**
+ * pty master of a `tty` app, its output is copied to the log file
+ * and to the attached client (`attach`) by a reader thread
+ */
+#[derive(Debug)]
+struct Pty {
+    master: File,
+    attached: Mutex<Option<(u64, UnixStream)>>,
+    /* tail of output, replayed on attach, so the screen is not empty */
+    scrollback: Mutex<Vec<u8>>,
+}
+
+const PTY_SCROLLBACK: usize = 8192;
+/* Ctrl-], like telnet */
+const DETACH_KEY: u8 = 0x1d;
+/* frames from `attach` client: [kind][u32 len][payload] */
+const ATTACH_FRAME_DATA: u8 = 0;
+const ATTACH_FRAME_WINSIZE: u8 = 1;
+/* client sends at most 1024 bytes a frame, anything longer is not from it */
+const ATTACH_FRAME_MAX: usize = 65536;
+
+impl Pty {
+    /**
+     * copy output until the app exits (read gets EIO when all slave fds are closed)
+     */
+    fn start_reader(self: &Arc<Self>, mut log: File) {
+        let pty = self.clone();
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = match (&pty.master).read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+                let _ = log.write_all(&buf[..n]);
+                {
+                    let mut scrollback = pty.scrollback.lock().unwrap();
+                    scrollback.extend_from_slice(&buf[..n]);
+                    let excess = scrollback.len().saturating_sub(PTY_SCROLLBACK);
+                    scrollback.drain(..excess);
                 }
-                Err(e) => {
-                    eprintln!("Error: {e}");
+                let mut attached = pty.attached.lock().unwrap();
+                if let Some((_, stream)) = attached.as_mut()
+                    && stream.write_all(&buf[..n]).is_err()
+                {
+                    *attached = None;
                 }
             }
-        }
-        Ok(())
+            /* app exited, let the attached client know by closing */
+            if let Some((_, stream)) = pty.attached.lock().unwrap().take() {
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+            }
+        });
     }
 
-    fn handle_client(self: &Self, mut stream: UnixStream) {
-        let message: Vec<String> =
-            bincode::decode_from_std_read(&mut stream, bincode::config::standard())
-                .expect("decode_from_std_read failed, maybe interface changed?");
+    /**
+     * serve an attached client until it detaches, an older client is kicked out
+     */
+    fn attach(&self, mut stream: UnixStream) -> std::io::Result<()> {
+        static ATTACH_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let id = ATTACH_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
-        let (command, params) = match message.as_slice() {
-            [first, rest @ ..] => (first, rest),
-            [] => panic!("Empty message received"),
-        };
+        /* a stuck client must not block the app's output */
+        let writer = stream.try_clone()?;
+        writer.set_write_timeout(Some(Duration::from_secs(1)))?;
+        {
+            let mut attached = self.attached.lock().unwrap();
+            if let Some((_, old)) = attached.take() {
+                let _ = old.shutdown(std::net::Shutdown::Both);
+            }
+            let mut writer = writer;
+            writer.write_all(&self.scrollback.lock().unwrap())?;
+            *attached = Some((id, writer));
+        }
 
-        match command.as_str() {
-            "status" => {
-                let _ = writeln!(stream, "Daemon is running.");
+        let result = (|| -> std::io::Result<()> {
+            loop {
+                let mut header = [0u8; 5];
+                match stream.read_exact(&mut header) {
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                    result => result?,
+                }
+                let len = u32::from_be_bytes(header[1..5].try_into().unwrap()) as usize;
+                if len > ATTACH_FRAME_MAX {
+                    /* can't find the next header after it, so give up the client */
+                    let _ = stream.shutdown(std::net::Shutdown::Both);
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("frame of {len} bytes, over {ATTACH_FRAME_MAX}"),
+                    ));
+                }
+                let mut payload = vec![0u8; len];
+                stream.read_exact(&mut payload)?;
+                match header[0] {
+                    ATTACH_FRAME_DATA => (&self.master).write_all(&payload)?,
+                    ATTACH_FRAME_WINSIZE if payload.len() == 4 => {
+                        let winsize = libc::winsize {
+                            ws_row: u16::from_be_bytes([payload[0], payload[1]]),
+                            ws_col: u16::from_be_bytes([payload[2], payload[3]]),
+                            ws_xpixel: 0,
+                            ws_ypixel: 0,
+                        };
+                        /* the app gets SIGWINCH */
+                        unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &winsize) };
+                    }
+                    _ => {}
+                }
             }
-            "add" => {
-                if params.len() >= 2 {
-                    let app_name = &params[0];
-                    let _ = writeln!(
+        })();
+
+        let mut attached = self.attached.lock().unwrap();
+        if attached.as_ref().is_some_and(|(attached_id, _)| *attached_id == id) {
+            *attached = None;
+        }
+        result
+    }
+}
+
+/**
+ * service: long-running, restarted whenever it exits (default)
+ * oneshot: run once when daemon starts (or by `run`), not restarted
+ * cron:    run on `schedule` (or by `run`), not restarted
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppType {
+    #[default]
+    Service,
+    Oneshot,
+    Cron,
+}
+
+impl AppConfig {
+    fn instances(&self) -> u32 {
+        self.instances.unwrap_or(1).max(1)
+    }
+
+    /**
+     * every field as (key, value in toml), "-" if not set, for `show`
+     */
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        fn repr<T: Serialize>(value: &T) -> String {
+            toml::Value::try_from(value)
+                .map(|value| value.to_string())
+                .unwrap_or("-".to_string())
+        }
+        let short_name = self.name.split_once('/').map_or(&*self.name, |(_, name)| name);
+        vec![
+            ("name", repr(&short_name)),
+            ("namespace", repr(&self.namespace)),
+            ("tags", repr(&self.tags)),
+            ("type", repr(&self.app_type)),
+            ("cmd", repr(&self.cmd)),
+            ("args", repr(&self.args)),
+            ("cwd", repr(&self.cwd)),
+            ("enabled", repr(&self.enabled)),
+            ("logdir", repr(&self.logdir)),
+            ("kill_signal", repr(&self.kill_signal)),
+            ("restart", repr(&self.restart)),
+            ("env", repr(&self.env)),
+            ("env_file", repr(&self.env_file)),
+            ("nice", repr(&self.sched.nice)),
+            ("ionice_class", repr(&self.sched.ionice_class)),
+            ("ionice_level", repr(&self.sched.ionice_level)),
+            ("cpu_affinity", repr(&self.sched.cpu_affinity)),
+            ("oom_score_adj", repr(&self.sched.oom_score_adj)),
+            ("cron_restart", repr(&self.cron_restart)),
+            ("schedule", repr(&self.schedule)),
+            ("overlap", repr(&self.overlap)),
+            ("instances", repr(&self.instances)),
+            ("port", repr(&self.port)),
+            ("ready_timeout", repr(&self.ready_timeout)),
+            ("listen", repr(&self.listen)),
+            ("on_demand", repr(&self.on_demand)),
+            ("notify", repr(&self.notify)),
+            ("watchdog_sec", repr(&self.watchdog_sec)),
+            ("hooks.pre_start", repr(&self.hooks.pre_start)),
+            ("hooks.post_start", repr(&self.hooks.post_start)),
+            ("hooks.pre_stop", repr(&self.hooks.pre_stop)),
+            ("hooks.post_stop", repr(&self.hooks.post_stop)),
+            ("hooks.timeout_sec", repr(&self.hooks.timeout_sec)),
+            ("stdin", repr(&self.stdin)),
+            ("tty", repr(&self.tty)),
+            ("reload_signal", repr(&self.reload_signal)),
+            ("reload_cmd", repr(&self.reload_cmd)),
+            ("watch", repr(&self.watch)),
+            ("watch_ignore", repr(&self.watch_ignore)),
+            ("watch_debounce_ms", repr(&self.watch_debounce_ms)),
+        ]
+    }
+
+    /**
+     * what serde can't check, for `load` and `edit`
+     */
+    fn validate(&self) -> Result<(), String> {
+        /* '.' joins namespace and name in file names, see `file_stem` */
+        if self.name.contains(['/', '.'])
+            || self.namespace.as_ref().is_some_and(|ns| ns.contains(['/', '.']))
+        {
+            return Err("name and namespace can't contain '/' or '.'".into());
+        }
+        if let Some(expr) = &self.cron_restart {
+            CronSchedule::from_str(expr).map_err(|e| format!("cron_restart: {e}"))?;
+        }
+        if let Some(mode) = &self.stdin
+            && !matches!(mode.as_str(), "null" | "pipe")
+            && !mode.starts_with("file:")
+        {
+            return Err(format!("stdin should be null, file:<path> or pipe, got {mode:?}"));
+        }
+        for pattern in &self.watch_ignore {
+            glob::Pattern::new(pattern).map_err(|e| format!("watch_ignore {pattern:?}: {e}"))?;
+        }
+        if let Some(signal) = &self.reload_signal {
+            parse_signal(signal).map_err(|e| format!("reload_signal: {e}"))?;
+        }
+        if let Some(signal) = &self.kill_signal {
+            parse_signal(signal).map_err(|e| format!("kill_signal: {e}"))?;
+        }
+        /* same checks as at spawn, so a bad edit is rejected before the app is stopped */
+        self.sched.resolve().map_err(|e| e.to_string())?;
+        if self.listen.len() > 64 {
+            return Err("too many `listen`".into());
+        }
+        if self.listen.iter().any(|listen| listen.tcp.is_some() == listen.unix.is_some()) {
+            return Err("each `listen` needs exactly one of `tcp` or `unix`".into());
+        }
+        match (&self.schedule, self.app_type) {
+            (Some(expr), _) => {
+                CronSchedule::from_str(expr).map_err(|e| format!("schedule: {e}"))?;
+            }
+            (None, AppType::Cron) => {
+                return Err("type = \"cron\" requires `schedule`".into());
+            }
+            (None, _) => {}
+        }
+        Ok(())
+    }
+
+    /**
+     * see `stdin`
+     */
+    fn stdin(&self) -> std::io::Result<Stdio> {
+        match self.stdin.as_deref() {
+            None => Ok(Stdio::inherit()),
+            Some("null") => Ok(Stdio::null()),
+            Some("pipe") => Ok(Stdio::piped()),
+            Some(mode) => match mode.strip_prefix("file:") {
+                Some(path) => Ok(Stdio::from(File::open(path)?)),
+                None => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("stdin should be null, file:<path> or pipe, got {mode:?}"),
+                )),
+            },
+        }
+    }
+
+    /**
+     * bind `listen` sockets if not yet, they are kept open by daemon from now on
+     */
+    fn bind_listen_sockets(&mut self) -> std::io::Result<()> {
+        if !self.listen_fds.is_empty() || self.listen.is_empty() {
+            return Ok(());
+        }
+        if self.listen.len() > 64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many `listen`"));
+        }
+        let mut fds = vec![];
+        for listen in &self.listen {
+            let fd: OwnedFd = match (&listen.tcp, &listen.unix) {
+                (Some(addr), None) => std::net::TcpListener::bind(addr)?.into(),
+                (None, Some(path)) => {
+                    /* stale socket file from last run */
+                    let _ = fs::remove_file(path);
+                    UnixListener::bind(path)?.into()
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "each `listen` needs exactly one of `tcp` or `unix`",
+                    ));
+                }
+            };
+            fds.push(fd);
+        }
+        self.listen_fds = fds;
+        Ok(())
+    }
+
+    fn notify_socket_path(&self) -> PathBuf {
+        PathBuf::from(format!("/tmp/pm-notify-{}.sock", self.file_stem()))
+    }
+
+    /**
+     * name usable in file names, "<namespace>.<name>" for apps in a namespace,
+     * unique as names can't contain '.'
+     */
+    fn file_stem(&self) -> String {
+        self.name.replace('/', ".")
+    }
+
+    /**
+     * bind NOTIFY_SOCKET if `notify` and not yet, with SO_PASSCRED to know sender's pid
+     */
+    fn bind_notify_socket(&mut self) -> std::io::Result<()> {
+        if self.notify_socket.is_some() || !self.notify.unwrap_or(false) {
+            return Ok(());
+        }
+        let path = self.notify_socket_path();
+        let _ = fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path)?;
+        let on: libc::c_int = 1;
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PASSCRED,
+                &on as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if ret == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        self.notify_socket = Some(socket);
+        Ok(())
+    }
+
+    /**
+     * `kill_signal`, SIGTERM if not set, or (with a warning) if it's not a signal
+     */
+    fn kill_signal(&self) -> nix::sys::signal::Signal {
+        let signal = self.kill_signal.as_deref().unwrap_or("SIGTERM");
+        parse_signal(signal).unwrap_or_else(|e| {
+            eprintln!("[pm][Warn] {}: kill_signal {e}, SIGTERM is used", self.name);
+            nix::sys::signal::Signal::SIGTERM
+        })
+    }
+
+    /**
+     * instance 0 logs to <name>.log, others to <name>-<id>.log
+     */
+    fn log_file(&self, instance: u32) -> PathBuf {
+        let log_name = match instance {
+            0 => format!("{}.log", self.file_stem()),
+            _ => format!("{}-{instance}.log", self.file_stem()),
+        };
+        self.logdir
+            .clone()
+            .unwrap_or(DEFAULT_LOG_DIR.into())
+            .join(log_name)
+    }
+}
+
+impl AppType {
+    fn is_service(&self) -> bool {
+        *self == AppType::Service
+    }
+}
+
+/**
+ * what to do if a oneshot/cron app is triggered while last run is still going
+ * skip:    ignore the new trigger (default)
+ * queue:   run again right after the current run finishes
+ * replace: stop the current run and start a new one
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapPolicy {
+    #[default]
+    Skip,
+    Queue,
+    Replace,
+}
+
+/**
+ * what watchdog does when a service exits
+ * always: restart it
+ * on-failure: restart it unless it exited with 0
+ * never: leave it stopped until `start`
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    Always,
+    OnFailure,
+    Never,
+}
+
+impl RestartPolicy {
+    fn should_restart(&self, exit_status: &ExitStatus) -> bool {
+        match self {
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => !exit_status.success(),
+            RestartPolicy::Never => false,
+        }
+    }
+}
+
+impl FromStr for RestartPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(RestartPolicy::Always),
+            "on-failure" => Ok(RestartPolicy::OnFailure),
+            "never" => Ok(RestartPolicy::Never),
+            _ => Err(format!("restart should be always, on-failure or never, got {s:?}")),
+        }
+    }
+}
+
+/**
+ * result of the last finished run of a oneshot/cron app,
+ * duration is taken when watchdog notices the exit, so it's up to one tick longer
+ */
+#[derive(Debug)]
+struct RunRecord {
+    started_at: time::SystemTime,
+    duration: Duration,
+    exit_status: ExitStatus,
+}
+
+impl std::fmt::Display for RunRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {:.1}s, {}",
+            format_local_time(self.started_at),
+            self.duration.as_secs_f64(),
+            describe_exit_status(self.exit_status)
+        )
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AppConfig {
+    /**
+     * name act as a unique identifier of the app,
+     * `add` refuses an existing name, unless `--force` which replaces the old one,
+     * app specific operation base on name,
+     * e.g. enable name1, disable name2
+     * If not provided, name will be same as path
+     */
+    #[serde(serialize_with = "serialize_short_name")]
+    pub name: String,
+    /**
+     * optional group, the app is then known as "<namespace>/<name>",
+     * so "api" can exist both in namespace "staging" and "prod".
+     * select all apps in it with "ns:<namespace>",
+     * neither of them may contain '/' or '.'
+     */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /* free labels, for selecting apps with "tag:<tag>", e.g. tags = ["video", "capture"] */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub cmd: String,
+    pub args: Vec<String>,
+    pub cwd: path::PathBuf,
+    pub enabled: bool,
+    pub logdir: Option<path::PathBuf>,
+    pub kill_signal: Option<String>,
+    /* what to do when a service exits, see `RestartPolicy`, default always */
+    pub restart: Option<RestartPolicy>,
+    /* extra environment, e.g. env = { RUST_LOG = "debug" }, wins over `env_file` */
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /* files of KEY=VALUE lines, relative to `cwd`, read at every spawn, later ones win */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env_file: Vec<PathBuf>,
+    /* nice, ionice, cpu_affinity, oom_score_adj, all optional */
+    #[serde(flatten)]
+    pub sched: SchedConfig,
+    /**
+     * restart the app periodically, standard 5 fields cron expression in local time,
+     * e.g. "30 3 * * *" restart at 03:30 every day, see `CronSchedule`
+     */
+    pub cron_restart: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "AppType::is_service")]
+    pub app_type: AppType,
+    /* cron expression for `type = "cron"`, same format as `cron_restart` */
+    pub schedule: Option<String>,
+    /* for oneshot/cron, default skip */
+    pub overlap: Option<OverlapPolicy>,
+    /**
+     * number of identical processes to run, default 1, can be changed by `scale`
+     * each one gets env PM_INSTANCE_ID=<0..instances-1>,
+     * and PORT=<port + PM_INSTANCE_ID> if `port` is set
+     */
+    pub instances: Option<u32>,
+    pub port: Option<u16>,
+    /**
+     * max seconds to wait for a new instance to be ready during `reload`, default 10
+     * ready means 127.0.0.1:<PORT> accepts connections if `port` is set,
+     * otherwise the process is still alive after 1 second
+     */
+    pub ready_timeout: Option<u64>,
+    /**
+     * sockets bound once by daemon and passed to every spawn of the app (and all instances),
+     * systemd style: fd 3, 4... with LISTEN_FDS, LISTEN_PID and LISTEN_FDNAMES,
+     * so connections are queued by kernel while the app is restarting
+     * e.g.
+     *      listen = [{ tcp = "0.0.0.0:8080" }, { name = "ctl", unix = "/tmp/app.sock" }]
+     */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listen: Vec<ListenConfig>,
+    /* with `listen`, don't start at boot, but when a socket gets the first connection */
+    pub on_demand: Option<bool>,
+    /**
+     * sd_notify support, the app gets NOTIFY_SOCKET and is online after sending READY=1,
+     * STATUS= is shown in `ls`, and with `watchdog_sec` it gets WATCHDOG_USEC,
+     * if it doesn't send WATCHDOG=1 in time, it's aborted and restarted
+     */
+    pub notify: Option<bool>,
+    pub watchdog_sec: Option<u64>,
+    #[serde(default, skip_serializing_if = "HooksConfig::is_empty")]
+    pub hooks: HooksConfig,
+    /**
+     * "null", "file:<path>" or "pipe", inherited from daemon if not set,
+     * with "pipe", daemon keeps the write end, and `send` writes lines to it
+     */
+    pub stdin: Option<String>,
+    /**
+     * run under a pseudo terminal owned by daemon, `stdin` is ignored,
+     * output is still logged, and `attach` connects your terminal to it
+     */
+    pub tty: Option<bool>,
+    /**
+     * how `reload-config` asks the app to re-read its config without restart,
+     * `reload_cmd` is run by /bin/sh with MAINPID set (like `hooks`, with its timeout),
+     * otherwise `reload_signal` is sent, SIGHUP if not set
+     */
+    pub reload_signal: Option<String>,
+    pub reload_cmd: Option<String>,
+    /**
+     * restart the app when a file under these paths changes (dirs are watched recursively),
+     * e.g. for development on the device
+     *      watch = ["/opt/app/bin/app", "/opt/app/conf"]
+     *      watch_ignore = ["*.swp", "*~", "/opt/app/conf/cache"]   # on file name or full path
+     *      watch_debounce_ms = 500     # default, restart once changes settled
+     */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch_ignore: Vec<String>,
+    pub watch_debounce_ms: Option<u64>,
+    #[serde(skip)]
+    exit_count: i32,
+    /* restarts of a running app by reason, "crash", "restart", "reload", "cron_restart" or "watch", for `show` */
+    #[serde(skip)]
+    restarts: BTreeMap<String, u32>,
+    #[serde(skip)]
+    next_cron_restart: Option<time::SystemTime>,
+    #[serde(skip)]
+    next_scheduled_run: Option<time::SystemTime>,
+    #[serde(skip)]
+    last_run: Option<RunRecord>,
+    #[serde(skip)]
+    run_queued: bool,
+    /**
+     * by `stop`, or not `enabled` at daemon start, it's not started
+     * (nor scheduled, nor on demand) until `start`, not saved
+     */
+    #[serde(skip)]
+    stopped: bool,
+    /* `watch <name> off`, not saved to pm.toml, kept across `daemon upgrade` */
+    #[serde(skip)]
+    watch_off: bool,
+    /* bound `listen` sockets, same order */
+    #[serde(skip)]
+    listen_fds: Vec<OwnedFd>,
+    #[serde(skip)]
+    notify_socket: Option<UnixDatagram>,
+}
+
+/**
+ * shell commands run by `sh -c` around start and stop (`start`, `restart`, `stop`...),
+ * not when watchdog restarts a crashed app.
+ * they get PM_APP_NAME, PM_HOOK, PM_APP_PID (space separated if multiple instances),
+ * and PM_EXIT_STATUS for post_stop, e.g. "exit 0", "signal 15 (SIGTERM)".
+ * a failing pre_start aborts the start, others only report the failure
+ * e.g.
+ *      [apps.hooks]
+ *      pre_start = "mkdir -p /tmp/app"
+ *      post_stop = "rm -f /tmp/app/lock"
+ *      timeout_sec = 10    # default 10, hook is killed after that
+ */
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HooksConfig {
+    pub pre_start: Option<String>,
+    pub post_start: Option<String>,
+    pub pre_stop: Option<String>,
+    pub post_stop: Option<String>,
+    pub timeout_sec: Option<u64>,
+}
+
+impl HooksConfig {
+    fn is_empty(&self) -> bool {
+        self.pre_start.is_none()
+            && self.post_start.is_none()
+            && self.pre_stop.is_none()
+            && self.post_stop.is_none()
+            && self.timeout_sec.is_none()
+    }
+
+    /**
+     * run hook `hook_name` if configured, Ok if not configured
+     */
+    fn run(
+        &self,
+        app_name: &str,
+        hook_name: &str,
+        envs: &[(&str, String)],
+    ) -> Result<(), String> {
+        let cmd = match hook_name {
+            "pre_start" => &self.pre_start,
+            "post_start" => &self.post_start,
+            "pre_stop" => &self.pre_stop,
+            "post_stop" => &self.post_stop,
+            _ => &None,
+        };
+        let Some(cmd) = cmd else {
+            return Ok(());
+        };
+        self.run_cmd(app_name, hook_name, cmd, envs)
+    }
+
+    /**
+     * run `cmd` with hook environment and `timeout_sec`
+     */
+    fn run_cmd(
+        &self,
+        app_name: &str,
+        hook_name: &str,
+        cmd: &str,
+        envs: &[(&str, String)],
+    ) -> Result<(), String> {
+        let timeout = Duration::from_secs(self.timeout_sec.unwrap_or(10));
+        println!("[pm][Info] {app_name}: run {hook_name} hook: {cmd}");
+
+        /* output goes to daemon log */
+        let mut hook = Command::new("/bin/sh")
+            .args(["-c", cmd])
+            .env("PM_APP_NAME", app_name)
+            .env("PM_HOOK", hook_name)
+            .envs(envs.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("{hook_name} hook failed to spawn: {e}"))?;
+        let started = time::Instant::now();
+        loop {
+            match hook.try_wait() {
+                Ok(Some(exit_status)) if exit_status.success() => return Ok(()),
+                Ok(Some(exit_status)) => {
+                    return Err(format!(
+                        "{hook_name} hook failed with {}",
+                        describe_exit_status(exit_status)
+                    ));
+                }
+                Ok(None) if started.elapsed() > timeout => {
+                    let _ = hook.kill();
+                    let _ = hook.wait();
+                    return Err(format!(
+                        "{hook_name} hook timed out after {}s",
+                        timeout.as_secs()
+                    ));
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(50)),
+                Err(e) => return Err(format!("{hook_name} hook: {e}")),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListenConfig {
+    /* LISTEN_FDNAMES entry, app name by default */
+    pub name: Option<String>,
+    pub tcp: Option<String>,
+    pub unix: Option<PathBuf>,
+}
+
+/**
+ * scheduling settings, applied to the child before exec, so whatever it starts gets them too,
+ * can also be changed on a running app with `sched`
+ * e.g.
+ *      nice = -5
+ *      ionice_class = "realtime"   # realtime | best-effort | idle
+ *      ionice_level = 0            # 0 (highest) ~ 7 (lowest)
+ *      cpu_affinity = "2-3"        # cpu list, same format as `taskset -c`
+ *      oom_score_adj = -1000       # -1000 means never be chosen by OOM killer
+ */
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SchedConfig {
+    pub nice: Option<i32>,
+    pub ionice_class: Option<String>,
+    pub ionice_level: Option<u8>,
+    pub cpu_affinity: Option<String>,
+    pub oom_score_adj: Option<i32>,
+}
+
+impl SchedConfig {
+    fn is_empty(&self) -> bool {
+        self.nice.is_none()
+            && self.ionice_class.is_none()
+            && self.ionice_level.is_none()
+            && self.cpu_affinity.is_none()
+            && self.oom_score_adj.is_none()
+    }
+
+    /**
+     * parse `key=value` pairs from cli, e.g. `nice=5 ionice=rt:2 cpus=0,2 oom=-1000`
+     */
+    fn parse_pairs(pairs: &[String]) -> Result<Self, String> {
+        let mut sched = Self::default();
+        for pair in pairs {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or(format!("[pm][Error] expect key=value, got {pair:?}"))?;
+            let bad_value = || format!("[pm][Error] invalid value for {key}: {value:?}");
+            match key {
+                "nice" => sched.nice = Some(value.parse().map_err(|_| bad_value())?),
+                "ionice" => {
+                    let (class, level) = match value.split_once(':') {
+                        Some((class, level)) => {
+                            (class, Some(level.parse().map_err(|_| bad_value())?))
+                        }
+                        None => (value, None),
+                    };
+                    sched.ionice_class = Some(class.to_string());
+                    sched.ionice_level = level;
+                }
+                "cpus" | "cpu_affinity" => sched.cpu_affinity = Some(value.to_string()),
+                "oom" | "oom_score_adj" => {
+                    sched.oom_score_adj = Some(value.parse().map_err(|_| bad_value())?)
+                }
+                _ => return Err(format!("[pm][Error] unknown sched setting {key:?}")),
+            }
+        }
+        Ok(sched)
+    }
+}
+
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /* this allows .toml to be empty */
+    #[serde(default)]
+    pub apps: Vec<AppConfig>,
+    #[serde(skip)]
+    config_filepath: path::PathBuf,
+}
+
+
+/**
+ * `name` is kept as "<namespace>/<name>" at runtime, in the file it's the bare name
+ */
+fn serialize_short_name<S: serde::Serializer>(name: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    let short = name.split_once('/').map_or(name, |(_, short)| short);
+    serializer.serialize_str(short)
+}
+
+impl Config {
+    pub fn load(&mut self) -> std::io::Result<()> {
+        match fs::read_to_string(&self.config_filepath) {
+            Ok(content) => {
+                let mut config: Self = toml::from_str(&content)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                for app in &config.apps {
+                    app.validate().map_err(|e| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("app {}: {e}", app.name),
+                        )
+                    })?;
+                }
+                /* from here on apps in a namespace go by "<namespace>/<name>" */
+                for app in &mut config.apps {
+                    if let Some(ns) = &app.namespace {
+                        app.name = format!("{ns}/{}", app.name);
+                    }
+                }
+                for (i, app) in config.apps.iter().enumerate() {
+                    if config.apps[..i].iter().any(|other| other.name == app.name) {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("app {}: defined more than once", app.name),
+                        ));
+                    }
+                }
+                // Ok(config)
+                self.apps = config.apps;
+                Ok(())
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                // File missing: use default
+                // Ok(self.default())
+                self.apps = self.default().apps;
+                Ok(())
+            }
+            Err(err) => Err(err), // Propagate other errors
+        }
+
+        // let content = fs::read_to_string(&self.config_filepath)?;
+        // let config = toml::from_str(&content)
+        //     .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        // Ok(config)
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.config_filepath)
+            .unwrap()
+            .write_all(&content.into_bytes())
+        // fs::write(&self.config_filepath, content)
+    }
+    pub fn add_config(&mut self, new_config: AppConfig) {
+        if let Some(old_config) = self.find_config(&new_config.name) {
+            *old_config = new_config;
+        } else {
+            self.apps.push(new_config);
+        }
+        self.save()
+            .unwrap_or_else(|_| eprintln!("[pm][Error] save config failed"));
+        // self.save().expect("???");
+        // eprintln!("[pm][Error] the App already exists")
+    }
+    pub fn remove_config(&mut self, name: &str) -> Option<AppConfig> {
+        let index = self.apps.iter().position(|app| app.name == name)?;
+        let removed = self.apps.remove(index);
+        self.save()
+            .unwrap_or_else(|_| eprintln!("[pm][Error] save config failed"));
+        Some(removed)
+    }
+    pub fn enable(&mut self, name: &str, enabled: bool) {
+        if let Some(appconfig) = self.find_config(name) {
+            appconfig.enabled = enabled;
+        }
+        self.save()
+            .unwrap_or_else(|_| eprintln!("[pm][Error] save config failed"));
+    }
+
+
+    pub fn find_config(&mut self, name: &str) -> std::option::Option<&mut AppConfig> {
+        self.apps.iter_mut().find(|i| i.name == name)
+    }
+
+
+    fn default(&self) -> Self {
+        Self {
+            apps: vec![],
+            config_filepath: self.config_filepath.clone(),
+        }
+    }
+}
+
+/**
+ * what an app told us through NOTIFY_SOCKET, see `AppConfig::notify`
+ */
+#[derive(Debug, Default)]
+struct NotifyState {
+    ready: bool,
+    reloading: bool,
+    stopping: bool,
+    status: Option<String>,
+    main_pid: Option<u32>,
+    last_watchdog: Option<time::Instant>,
+}
+
+#[derive(Clone)]
+struct ProcessManagerDaemon {
+    config: Arc<Mutex<Config>>,
+    processes_table: Arc<Mutex<Vec<ProcessChild>>>,
+    /* by pid of the child, lock after config and processes_table */
+    notify_states: Arc<Mutex<HashMap<u32, NotifyState>>>,
+    /* lock last, after all above */
+    exit_history: Arc<Mutex<ExitHistory>>,
+    /* of /tmp/pm.sock, kept across `daemon upgrade` */
+    listener_fd: Arc<Mutex<Option<RawFd>>>,
+}
+
+const DEFAULT_LOG_DIR: &str = "/tmp";
+
+impl ProcessManagerDaemon {
+    /**
+     * `upgraded`: exec by `daemon upgrade`, running apps are still our children
+     */
+    pub fn new(config_filepath: &path::Path, upgraded: bool) -> ProcessManagerDaemon {
+        let new_pmd = Self {
+            config: Arc::new(Mutex::new(Config {
+                apps: vec![],
+                config_filepath: config_filepath.to_path_buf(),
+            })),
+            processes_table: Arc::new(Mutex::new(Vec::<ProcessChild>::new())),
+            notify_states: Arc::new(Mutex::new(HashMap::new())),
+            exit_history: Arc::new(Mutex::new(ExitHistory::load())),
+            listener_fd: Arc::new(Mutex::new(None)),
+        };
+
+        new_pmd
+            .config
+            .lock()
+            .unwrap()
+            .load()
+            .expect("why load config failed?");
+
+        /* before starting, so only missing instances are spawned,
+         * and before binding, so upgraded daemon reuses sockets of apps */
+        let adopted = StateFile::adopt(&mut new_pmd.config.lock().unwrap(), upgraded);
+        for app_config in new_pmd.config.lock().unwrap().apps.iter_mut() {
+            app_config.bind_listen_sockets().unwrap_or_else(|e| {
+                eprintln!("[pm][Error] {} failed to bind listen sockets: {e}", app_config.name)
+            });
+            app_config.bind_notify_socket().unwrap_or_else(|e| {
+                eprintln!("[pm][Error] {} failed to bind notify socket: {e}", app_config.name)
+            });
+        }
+
+        for process_child in &adopted {
+            let notify = new_pmd
+                .config
+                .lock()
+                .unwrap()
+                .find_config(&process_child.name)
+                .is_some_and(|app_config| app_config.notify.unwrap_or(false));
+            /* it was up before, READY won't be sent again */
+            if notify {
+                new_pmd.notify_states.lock().unwrap().insert(
+                    process_child.child.id(),
+                    NotifyState {
+                        ready: true,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        new_pmd.processes_table.lock().unwrap().extend(adopted);
+
+        new_pmd.start_all_apps();
+        new_pmd.start_watchdog_loop();
+        new_pmd.start_cron_loop();
+        new_pmd.start_on_demand_loop();
+        new_pmd.start_notify_loop();
+        new_pmd.start_watch_loop();
+
+        new_pmd
+    }
+    /**
+     * `inherited_fd`: listener kept by `daemon upgrade`, clients don't see it closed
+     */
+    pub fn start_listening(self: &Self, inherited_fd: Option<RawFd>) -> std::io::Result<()> {
+        let listener = match inherited_fd {
+            Some(fd) => {
+                set_cloexec(fd, true)?;
+                unsafe { UnixListener::from_raw_fd(fd) }
+            }
+            None => {
+                // Remove previous socket file if it exists
+                let _ = fs::remove_file(SOCKET_PATH);
+                UnixListener::bind(SOCKET_PATH)?
+            }
+        };
+        *self.listener_fd.lock().unwrap() = Some(listener.as_raw_fd());
+        println!("Daemon listening on {}", SOCKET_PATH);
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let handler = self.clone();
+                    /* FIXME\: thread and struct...? */
+                    thread::spawn(move || handler.handle_client(stream));
+                }
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_client(self: &Self, mut stream: UnixStream) {
+        let message: Vec<String> =
+            bincode::decode_from_std_read(&mut stream, bincode::config::standard())
+                .expect("decode_from_std_read failed, maybe interface changed?");
+
+        let (command, params) = match message.as_slice() {
+            [first, rest @ ..] => (first, rest),
+            [] => panic!("Empty message received"),
+        };
+
+        /* first param selects apps, the command runs once for each of them */
+        let selector = params.first().filter(|_| SELECTOR_COMMANDS.contains(&command.as_str()));
+        let Some(selector) = selector else {
+            return self.handle_command(command, params, &mut stream);
+        };
+        let app_names = match self.select_apps(selector) {
+            Ok(app_names) => app_names,
+            Err(e) => {
+                let _ = writeln!(stream, "{e}");
+                return;
+            }
+        };
+        if matches!(command.as_str(), "attach" | "edit") && app_names.len() > 1 {
+            let _ = writeln!(
+                stream,
+                "[pm][Error] `{selector}` matches {} apps, {command} needs one",
+                app_names.len()
+            );
+            return;
+        }
+        for app_name in app_names {
+            let params: Vec<String> = std::iter::once(app_name)
+                .chain(params[1..].iter().cloned())
+                .collect();
+            self.handle_command(command, &params, &mut stream);
+        }
+    }
+
+    /**
+     * names of apps `selector` matches, in config order, error if none:
+     * "all", a glob like "worker-*", "tag:video", "ns:staging", a name,
+     * or index in `ls` (1-based), a name made of digits goes before the index
+     */
+    fn select_apps(self: &Self, selector: &str) -> Result<Vec<String>, String> {
+        let config_lock = self.config.lock().unwrap();
+        let apps = config_lock.apps.iter();
+        let app_names: Vec<String> = if selector == "all" {
+            apps.map(|app| app.name.clone()).collect()
+        } else if let Some(tag) = selector.strip_prefix("tag:") {
+            apps.filter(|app| app.tags.iter().any(|t| t == tag))
+                .map(|app| app.name.clone())
+                .collect()
+        } else if let Some(ns) = selector.strip_prefix("ns:") {
+            apps.filter(|app| app.namespace.as_deref() == Some(ns))
+                .map(|app| app.name.clone())
+                .collect()
+        } else if config_lock.apps.iter().any(|app| app.name == selector) {
+            vec![selector.to_string()]
+        } else if let Ok(index) = selector.parse::<usize>() {
+            apps.enumerate()
+                .filter(|(i, _)| i + 1 == index)
+                .map(|(_, app)| app.name.clone())
+                .collect()
+        } else if selector.contains(['*', '?', '[']) {
+            let pattern = glob::Pattern::new(selector)
+                .map_err(|e| format!("[pm][Error] invalid pattern `{selector}`: {e}"))?;
+            apps.filter(|app| pattern.matches(&app.name))
+                .map(|app| app.name.clone())
+                .collect()
+        } else {
+            vec![]
+        };
+        match app_names.is_empty() {
+            true => Err(format!("[pm][Error] no app matches `{selector}`")),
+            false => Ok(app_names),
+        }
+    }
+
+    fn handle_command(self: &Self, command: &str, params: &[String], stream: &mut UnixStream) {
+        match command {
+            "status" => {
+                let _ = writeln!(stream, "Daemon is running.");
+            }
+            "add" => {
+                let (app_config, no_start, force) = match parse_add_params(params) {
+                    Ok(parsed) => parsed,
+                    Err(e) => {
+                        let _ = writeln!(stream, "[pm][Error] {e}");
+                        return;
+                    }
+                };
+                let app_name = app_config.name.clone();
+                let exists = self.config.lock().unwrap().find_config(&app_name).is_some();
+                if exists && !force {
+                    let _ = writeln!(
                         stream,
-                        "Add new App {:?}, cmd: {:?}",
-                        app_name,
-                        &params[1..],
+                        "[pm][Error] {app_name} already exists, add --force to replace it"
+                    );
+                    return;
+                }
+                if exists && let Ok(result) = self.try_stop_app_by_name(&app_name, ExitTrigger::Stop) {
+                    let _ = writeln!(stream, "{result}");
+                }
+                let _ = writeln!(
+                    stream,
+                    "Add new App {:?}, cmd: {:?}",
+                    app_name,
+                    std::iter::once(&app_config.cmd).chain(&app_config.args).collect::<Vec<_>>(),
+                );
+                self.config.lock().unwrap().add_config(app_config);
+                if no_start {
+                    let _ = writeln!(stream, "[pm][Info] {app_name} added, not started");
+                    return;
+                }
+                let result = self.try_start_app_by_name(&app_name).unwrap_or_else(|e| e);
+                let _ = writeln!(stream, "{result}");
+            }
+            "remove" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: remove <name> [--delete-logs]");
+                    return;
+                };
+                let delete_logs = params[1..].iter().any(|p| p == "--delete-logs");
+                let result = self
+                    .try_remove_app_by_name(app_name, delete_logs)
+                    .unwrap_or_else(|e| e);
+                let _ = writeln!(stream, "{result}");
+            }
+            "start" | "stop" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: {command} <name>");
+                    return;
+                };
+                let result = match command {
+                    "start" => self.start_app_by_name(app_name),
+                    _ => self.stop_app_by_name(app_name),
+                };
+                let _ = writeln!(stream, "{}", result.unwrap_or_else(|e| e));
+            }
+            "sched" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(
+                        stream,
+                        "usage: sched <name> [nice=N] [ionice=CLASS[:LEVEL]] [cpus=LIST] [oom=N]"
+                    );
+                    return;
+                };
+                let result = SchedConfig::parse_pairs(&params[1..])
+                    .and_then(|sched| self.try_sched_app_by_name(app_name, &sched));
+                let _ = writeln!(stream, "{}", result.unwrap_or_else(|e| e));
+            }
+            "run" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: run <name>");
+                    return;
+                };
+                let result = self.try_run_app_by_name(app_name).unwrap_or_else(|e| e);
+                let _ = writeln!(stream, "{result}");
+            }
+            "reload" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: reload <name>");
+                    return;
+                };
+                let result = self.try_reload_app_by_name(app_name, &mut |line| {
+                    let _ = writeln!(stream, "{line}");
+                });
+                let _ = writeln!(stream, "{}", result.unwrap_or_else(|e| e));
+            }
+            "signal" => {
+                let (Some(app_name), Some(signal)) = (params.first(), params.get(1)) else {
+                    let _ = writeln!(stream, "usage: signal <name> <SIGNAME|number>");
+                    return;
+                };
+                let result = parse_signal(signal)
+                    .map_err(|e| format!("[pm][Error] {e}"))
+                    .and_then(|signal| self.try_signal_app_by_name(app_name, signal));
+                let _ = writeln!(stream, "{}", result.unwrap_or_else(|e| e));
+            }
+            "reload-config" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: reload-config <name>");
+                    return;
+                };
+                let result = self
+                    .try_reload_config_app_by_name(app_name)
+                    .unwrap_or_else(|e| e);
+                let _ = writeln!(stream, "{result}");
+            }
+            "pause" | "resume" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: {command} <name>");
+                    return;
+                };
+                let result = self
+                    .try_pause_app_by_name(app_name, command == "pause")
+                    .unwrap_or_else(|e| e);
+                let _ = writeln!(stream, "{result}");
+            }
+            "watch" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: watch <name> [on|off]");
+                    return;
+                };
+                let mut config_lock = self.config.lock().unwrap();
+                let Some(app_config) = config_lock.find_config(app_name) else {
+                    let _ = writeln!(stream, "[pm][Warn] {app_name} not found");
+                    return;
+                };
+                if app_config.watch.is_empty() {
+                    let _ = writeln!(stream, "[pm][Warn] {app_name} has no `watch` paths in config");
+                    return;
+                }
+                match params.get(1).map(String::as_str) {
+                    Some("on") => app_config.watch_off = false,
+                    Some("off") => app_config.watch_off = true,
+                    None => {}
+                    Some(_) => {
+                        let _ = writeln!(stream, "usage: watch <name> [on|off]");
+                        return;
+                    }
+                }
+                let _ = writeln!(
+                    stream,
+                    "[pm][Info] watching {app_name} is {}: {}",
+                    if app_config.watch_off { "off" } else { "on" },
+                    app_config
+                        .watch
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            "doctor" => {
+                let (Some("orphans"), kill) = (
+                    params.first().map(String::as_str),
+                    params.get(1).is_some_and(|p| p == "--kill"),
+                ) else {
+                    let _ = writeln!(stream, "usage: doctor orphans [--kill]");
+                    return;
+                };
+                let orphans = self.find_orphans();
+                if orphans.is_empty() {
+                    let _ = writeln!(stream, "[pm][Info] no orphans found");
+                    return;
+                }
+                for orphan in &orphans {
+                    let _ = writeln!(stream, "{orphan}");
+                }
+                if kill {
+                    let pids: Vec<u32> = orphans.iter().map(|orphan| orphan.pid).collect();
+                    let _ = writeln!(stream, "{}", kill_orphans(&pids));
+                } else {
+                    let _ = writeln!(stream, "[pm][Info] run `doctor orphans --kill` to kill them");
+                }
+            }
+            "history" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: history <apps>");
+                    return;
+                };
+                use tabled::{builder::Builder, settings::Style};
+
+                let mut b = Builder::new();
+                b.push_record(["Time", "Instance", "Pid", "Exit", "Core", "Uptime", "Trigger"]);
+                for record in self
+                    .exit_history
+                    .lock()
+                    .unwrap()
+                    .apps
+                    .get(app_name)
+                    .into_iter()
+                    .flatten()
+                {
+                    let exit_status = record.exit_status();
+                    b.push_record([
+                        format_local_time(record.time()),
+                        format!("#{}", record.instance),
+                        record.pid.to_string(),
+                        match exit_status {
+                            None => "unknown".to_string(),
+                            Some(status) => match (status.code(), status.signal()) {
+                                (Some(code), _) => format!("exit {code}"),
+                                (_, Some(sig)) => nix::sys::signal::Signal::try_from(sig)
+                                    .map(|s| s.as_str().to_string())
+                                    .unwrap_or(format!("signal {sig}")),
+                                _ => status.to_string(),
+                            },
+                        },
+                        match exit_status.is_some_and(|status| status.core_dumped()) {
+                            true => "√".to_string(),
+                            false => String::new(),
+                        },
+                        format_duration(Duration::from_secs(record.uptime_secs)),
+                        record.trigger.to_string(),
+                    ]);
+                }
+                let mut table = b.build();
+                table.with(Style::sharp());
+                let _ = writeln!(stream, "{app_name}\n{table}");
+            }
+            "show" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: show <apps>");
+                    return;
+                };
+                let result = self.show_app_by_name(app_name).unwrap_or_else(|e| e);
+                let _ = writeln!(stream, "{result}");
+            }
+            "edit" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: edit <app> [toml]");
+                    return;
+                };
+                /* without toml, send the app as toml for `pm edit` to open */
+                let Some(snippet) = params.get(1) else {
+                    let config_lock = self.config.lock().unwrap();
+                    let Some(app_config) = config_lock.apps.iter().find(|app| &app.name == app_name) else {
+                        let _ = writeln!(stream, "[pm][Warn] {app_name} not found");
+                        return;
+                    };
+                    match toml::to_string_pretty(app_config) {
+                        Ok(snippet) => {
+                            let _ = write!(stream, "{snippet}");
+                        }
+                        Err(e) => {
+                            let _ = writeln!(stream, "[pm][Error] {e}");
+                        }
+                    }
+                    return;
+                };
+                let result = self
+                    .try_edit_app_by_name(app_name, snippet)
+                    .unwrap_or_else(|e| e);
+                let _ = writeln!(stream, "{result}");
+            }
+            "attach" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: attach <name> [instance]");
+                    return;
+                };
+                let instance = params.get(1).and_then(|i| i.parse().ok()).unwrap_or(0);
+                let pty = self
+                    .processes_table
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|p| p.name == *app_name && p.instance == instance)
+                    .map(|p| p.pty.clone());
+                match pty {
+                    Some(Some(pty)) => {
+                        /* client switches to raw mode after this line */
+                        let _ = writeln!(
+                            stream,
+                            "[pm][Info] attached to {app_name}, detach with Ctrl-]\r"
+                        );
+                        if let Err(e) = stream.try_clone().and_then(|stream| pty.attach(stream)) {
+                            eprintln!("[pm][Warn] attach to {app_name}: {e}");
+                        }
+                    }
+                    Some(None) => {
+                        let _ = writeln!(stream, "[pm][Warn] {app_name} is not a tty app");
+                    }
+                    None => {
+                        let _ = writeln!(stream, "[pm][Warn] {app_name} seems not started");
+                    }
+                }
+            }
+            "send" => {
+                let (Some(app_name), [_, text @ ..]) = (params.first(), params) else {
+                    let _ = writeln!(stream, "usage: send <name> <text>...");
+                    return;
+                };
+                let result = self
+                    .try_send_app_by_name(app_name, &text.join(" "))
+                    .unwrap_or_else(|e| e);
+                let _ = writeln!(stream, "{result}");
+            }
+            "scale" => {
+                let n = params.get(1).and_then(|n| n.parse::<u32>().ok());
+                let (Some(app_name), Some(n)) = (params.first(), n.filter(|n| *n >= 1)) else {
+                    let _ = writeln!(stream, "usage: scale <name> <instances>, instances >= 1");
+                    return;
+                };
+                let result = self.try_scale_app_by_name(app_name, n).unwrap_or_else(|e| e);
+                let _ = writeln!(stream, "{result}");
+            }
+            "ls" => {
+                /* TODO\: show as a beautiful table */
+                // let _ = writeln!(stream, "{:#?}", self.processes_table.lock().unwrap());
+                // let _ = writeln!(stream, "{:#?}", self.config.lock().unwrap());
+
+                if false {
+                    use prettytable::{Table, row};
+                    let mut table = Table::new();
+                    table.add_row(row!["Config Path"]);
+                    table.add_row(row![self.config.lock().unwrap().config_filepath.display()]);
+                    let _ = writeln!(stream, "{}", table);
+
+                    let mut table = Table::new();
+                    table.add_row(row![
+                        "", "Name", "Enabled", "Command", "Args", "Cwd", "Log Dir"
+                    ]);
+                    for (i, app) in self.config.lock().unwrap().apps.iter().enumerate() {
+                        table.add_row(row![
+                            i + 1,
+                            app.name,
+                            if app.enabled { "√" } else { "" },
+                            app.cmd,
+                            app.args.join(""),
+                            app.cwd.display(),
+                            app.logdir
+                                .as_ref()
+                                .unwrap_or(&PathBuf::from(DEFAULT_LOG_DIR))
+                                .display()
+                        ]);
+                    }
+                    let _ = write!(stream, "{}", table);
+                }
+
+                {
+                    use tabled::{builder::Builder, settings::Style};
+
+                    let mut selector = None;
+                    let mut group_by = None;
+                    let mut args = params.iter();
+                    while let Some(arg) = args.next() {
+                        match arg.as_str() {
+                            "--group-by" => group_by = args.next().map(String::as_str),
+                            _ => selector = Some(arg.as_str()),
+                        }
+                    }
+                    if let Some(group_by) = group_by
+                        && !matches!(group_by, "tag" | "namespace")
+                    {
+                        let _ = writeln!(
+                            stream,
+                            "[pm][Error] can group by tag or namespace, not `{group_by}`"
+                        );
+                        return;
+                    }
+                    let selected = match selector.map(|selector| self.select_apps(selector)) {
+                        Some(Err(e)) => {
+                            let _ = writeln!(stream, "{e}");
+                            return;
+                        }
+                        Some(Ok(app_names)) => Some(app_names),
+                        None => None,
+                    };
+
+                    let mut b = Builder::new();
+                    b.push_column([
+                        "Config Path",
+                        self.config
+                            .lock()
+                            .unwrap()
+                            .config_filepath
+                            .display()
+                            .to_string()
+                            .as_str(),
+                    ]);
+                    let mut table = b.build();
+                    table.with(Style::modern());
+                    let _ = writeln!(stream, "{}", table);
+
+                    let config_lock = self.config.lock().unwrap();
+                    let apps = config_lock.apps.iter().enumerate().filter(|(_, app)| {
+                        selected
+                            .as_ref()
+                            .is_none_or(|app_names| app_names.contains(&app.name))
+                    });
+                    /* one table per namespace or tag, an app with several tags shows up in each */
+                    let mut groups: BTreeMap<Option<String>, Vec<(usize, &AppConfig)>> =
+                        BTreeMap::new();
+                    for (i, app) in apps {
+                        let keys = match group_by {
+                            Some("namespace") => {
+                                vec![Some(app.namespace.clone().unwrap_or("(none)".into()))]
+                            }
+                            Some(_) if app.tags.is_empty() => vec![Some("(untagged)".into())],
+                            Some(_) => app.tags.iter().cloned().map(Some).collect(),
+                            None => vec![None],
+                        };
+                        for key in keys {
+                            groups.entry(key).or_default().push((i, app));
+                        }
+                    }
+                    if groups.is_empty() {
+                        groups.insert(None, vec![]);
+                    }
+                    for (title, apps) in &groups {
+                        if let (Some(group_by), Some(title)) = (group_by, title) {
+                            let _ = writeln!(stream, "{group_by}: {title}");
+                        }
+                        let mut b = Builder::new();
+                        b.push_record([
+                            "",
+                            "Name",
+                            "Type",
+                            "Tags",
+                            "Enabled",
+                            "Running",
+                            "Status",
+                            "Exit Count",
+                            "Cmd",
+                            "Args",
+                            "Cwd",
+                            "Log Dir",
+                            "Kill Signal",
+                            "Sched",
+                            "Next Scheduled",
+                            "Last Run",
+                        ]);
+                        for &(i, app) in apps {
+                            /* (instance, status, pid) of each started instance */
+                            let instances: Vec<(u32, &str, Option<u32>)> = {
+                                let mut table_lock = self.processes_table.lock().unwrap();
+                                let mut instances: Vec<_> = table_lock
+                                    .iter_mut()
+                                    .filter(|x| x.name == app.name)
+                                    .map(|process_child| {
+                                        let (status, pid) = match process_child.child.try_wait() {
+                                            Ok(Some(_)) => ("Exited", None),
+                                            Ok(None) if process_child.paused.is_some() => {
+                                                ("Paused", Some(process_child.child.id()))
+                                            }
+                                            Ok(None) => ("Running", Some(process_child.child.id())),
+                                            Err(_) => ("Error", None),
+                                        };
+                                        (process_child.instance, status, pid)
+                                    })
+                                    .collect();
+                                instances.sort_by_key(|(instance, _, _)| *instance);
+                                instances
+                            };
+                            let running = instances
+                                .iter()
+                                .filter(|(_, status, _)| *status == "Running")
+                                .count();
+                            let status = match instances.first() {
+                                None if app.last_run.is_some() => "Finished".to_string(),
+                                None if app.stopped => "Stopped".to_string(),
+                                None => "Not Started".to_string(),
+                                Some((_, status, _)) if app.instances() == 1 => status.to_string(),
+                                Some(_) => format!("Running {running}/{}", app.instances()),
+                            };
+                            let pid = instances.first().and_then(|(_, _, pid)| *pid);
+                            let status = match (app.instances(), pid) {
+                                (1, Some(pid)) => self.describe_notify_state(app, pid, &status),
+                                _ => status,
+                            };
+                            b.push_record([
+                                (i + 1).to_string().as_str(),
+                                &app.name,
+                                &format!("{:?}", app.app_type).to_lowercase(),
+                                &app.tags.join(","),
+                                if app.enabled { "√" } else { "" },
+                                if running > 0 { "√" } else { "" },
+                                &status,
+                                &app.exit_count.to_string(),
+                                &app.cmd,
+                                &app.args.join(""),
+                                &app.cwd.display().to_string(),
+                                &app.logdir
+                                    .as_ref()
+                                    .unwrap_or(&PathBuf::from(DEFAULT_LOG_DIR))
+                                    .display()
+                                    .to_string(),
+                                &app.kill_signal
+                                    .as_ref()
+                                    .unwrap_or(&"SIGTERM".to_string())
+                                    .to_string(),
+                                &pid.map(read_sched_settings).unwrap_or_default(),
+                                &app.next_cron_restart
+                                    .or(app.next_scheduled_run)
+                                    .map(format_local_time)
+                                    .unwrap_or_default(),
+                                &app.last_run
+                                    .as_ref()
+                                    .map(RunRecord::to_string)
+                                    .unwrap_or_default(),
+                            ]);
+                            /* instances grouped under the app */
+                            if app.instances() > 1 {
+                                for (instance, status, pid) in &instances {
+                                    let mut record = vec![String::new(); 16];
+                                    record[1] = format!(" └ #{instance}");
+                                    record[6] = match pid {
+                                        Some(pid) => format!(
+                                            "{} (pid {pid})",
+                                            self.describe_notify_state(app, *pid, status)
+                                        ),
+                                        None => status.to_string(),
+                                    };
+                                    record[13] = pid.map(read_sched_settings).unwrap_or_default();
+                                    b.push_record(record);
+                                }
+                            }
+                        }
+
+                        let mut table = b.build();
+                        table.with(Style::modern());
+                        let _ = writeln!(stream, "{}", table);
+                    }
+                }
+            }
+            "restart" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: restart <apps>");
+                    return;
+                };
+                let _ = writeln!(stream, "[pm][Info] restarting {app_name}... ");
+                let result = self.restart_app_by_name(app_name, ExitTrigger::Stop);
+                let _ = writeln!(stream, "{result}");
+            }
+            "enable" | "disable" => {
+                let Some(app_name) = params.first() else {
+                    let _ = writeln!(stream, "usage: {command} <apps>");
+                    return;
+                };
+                let result = self.try_enable_app_by_name(app_name, command == "enable");
+                let _ = writeln!(stream, "{result}");
+            }
+            /* spawn all */
+            "on" => {}
+            "upgrade" => {
+                let _ = writeln!(stream, "[pm][Info] upgrading daemon...");
+                let e = self.upgrade();
+                let _ = writeln!(stream, "[pm][Error] daemon upgrade failed: {e}");
+            }
+            "quit" => {
+                let _ = writeln!(stream, "[pm][Info] stopping all apps...");
+                self.stop_all_apps();
+                let _ = writeln!(stream, "[pm][Info] all apps stopped");
+                std::process::exit(0);
+            }
+            cmd => {
+                let _ = writeln!(stream, "[pm][Warn] Unknown command: {cmd}");
+            }
+        }
+    }
+
+    /**
+     * restart apps with `watch` on changes through the same path as `restart` command,
+     * inotify watches are rebuilt when `watch` settings of apps change
+     */
+    fn start_watch_loop(self: &Self) {
+        use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+
+        /* a watched dir, `only` is set if the watched path is a file in it,
+         * a dir may be watched for several files or apps, all of them are kept per wd */
+        struct WatchTarget {
+            app_name: String,
+            dir: PathBuf,
+            only: Option<std::ffi::OsString>,
+            ignore: Vec<glob::Pattern>,
+        }
+
+        fn ignored(ignore: &[glob::Pattern], path: &Path) -> bool {
+            let file_name = path.file_name().map(Path::new).unwrap_or(path);
+            ignore
+                .iter()
+                .any(|pattern| pattern.matches_path(path) || pattern.matches_path(file_name))
+        }
+
+        /* IN_MASK_ADD (not in nix), so the mask of a dir watched before is extended, not replaced */
+        fn add_target(
+            inotify: &Inotify,
+            targets: &mut HashMap<WatchDescriptor, Vec<WatchTarget>>,
+            mask: AddWatchFlags,
+            target: WatchTarget,
+        ) -> nix::Result<()> {
+            let wd = inotify.add_watch(&target.dir, mask | AddWatchFlags::from_bits_retain(libc::IN_MASK_ADD))?;
+            targets.entry(wd).or_default().push(target);
+            Ok(())
+        }
+
+        /* the dir and its subdirs, except ignored ones */
+        fn add_dir(
+            inotify: &Inotify,
+            targets: &mut HashMap<WatchDescriptor, Vec<WatchTarget>>,
+            app_name: &str,
+            dir: &Path,
+            ignore: &[glob::Pattern],
+        ) {
+            let mask = AddWatchFlags::IN_CLOSE_WRITE
+                | AddWatchFlags::IN_MOVED_TO
+                | AddWatchFlags::IN_MOVED_FROM
+                | AddWatchFlags::IN_CREATE
+                | AddWatchFlags::IN_DELETE;
+            let target = WatchTarget {
+                app_name: app_name.to_string(),
+                dir: dir.to_path_buf(),
+                only: None,
+                ignore: ignore.to_vec(),
+            };
+            if let Err(e) = add_target(inotify, targets, mask, target) {
+                eprintln!("[pm][Warn] {app_name}: failed to watch {}: {e}", dir.display());
+                return;
+            }
+            for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if entry.file_type().is_ok_and(|t| t.is_dir()) && !ignored(ignore, &path) {
+                    add_dir(inotify, targets, app_name, &path, ignore);
+                }
+            }
+        }
+
+        let handler = self.clone();
+        thread::spawn(move || {
+            /* (app name, watch paths, ignore) the current watches are built from */
+            let mut watched: Vec<(String, Vec<PathBuf>, Vec<String>)> = vec![];
+            let mut inotify: Option<Inotify> = None;
+            let mut targets: HashMap<WatchDescriptor, Vec<WatchTarget>> = HashMap::new();
+            /* app name => (last change, changed path) */
+            let mut pending: HashMap<String, (time::Instant, PathBuf)> = HashMap::new();
+            loop {
+                let (settings, debounces): (Vec<_>, HashMap<String, Duration>) = {
+                    let config_lock = handler.config.lock().unwrap();
+                    let apps = config_lock.apps.iter().filter(|app| !app.watch.is_empty());
+                    (
+                        apps.clone()
+                            .map(|app| (app.name.clone(), app.watch.clone(), app.watch_ignore.clone()))
+                            .collect(),
+                        apps.filter(|app| !app.watch_off)
+                            .map(|app| {
+                                let debounce = app.watch_debounce_ms.unwrap_or(500);
+                                (app.name.clone(), Duration::from_millis(debounce))
+                            })
+                            .collect(),
+                    )
+                };
+                if settings != watched {
+                    targets.clear();
+                    inotify = None;
+                    if !settings.is_empty() {
+                        match Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC) {
+                            Ok(new_inotify) => {
+                                for (app_name, paths, ignore) in &settings {
+                                    let ignore: Vec<glob::Pattern> = ignore
+                                        .iter()
+                                        .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+                                        .collect();
+                                    for path in paths {
+                                        if path.is_dir() {
+                                            add_dir(&new_inotify, &mut targets, app_name, path, &ignore);
+                                            continue;
+                                        }
+                                        /* watch the parent, a rebuilt file is often a new inode */
+                                        let dir = path.parent().unwrap_or(Path::new("/"));
+                                        let mask = AddWatchFlags::IN_CLOSE_WRITE
+                                            | AddWatchFlags::IN_MOVED_TO
+                                            | AddWatchFlags::IN_CREATE
+                                            | AddWatchFlags::IN_DELETE;
+                                        let target = WatchTarget {
+                                            app_name: app_name.clone(),
+                                            dir: dir.to_path_buf(),
+                                            only: path.file_name().map(Into::into),
+                                            ignore: ignore.clone(),
+                                        };
+                                        if let Err(e) =
+                                            add_target(&new_inotify, &mut targets, mask, target)
+                                        {
+                                            eprintln!(
+                                                "[pm][Warn] {app_name}: failed to watch {}: {e}",
+                                                path.display()
+                                            );
+                                        }
+                                    }
+                                }
+                                inotify = Some(new_inotify);
+                            }
+                            Err(e) => eprintln!("[pm][Error] inotify init failed: {e}"),
+                        }
+                    }
+                    watched = settings;
+                }
+
+                let Some(inotify) = &inotify else {
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                };
+                let mut pollfd = libc::pollfd {
+                    fd: std::os::fd::AsFd::as_fd(inotify).as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                /* timeout, for debounce and settings changes */
+                unsafe { libc::poll(&mut pollfd, 1, 100) };
+                for event in inotify.read_events().unwrap_or_default() {
+                    let (Some(wd_targets), Some(name)) = (targets.get(&event.wd), &event.name) else {
+                        continue;
+                    };
+                    /* (app name, path, ignore) of new subdirs, added after the loop */
+                    let mut new_dirs = vec![];
+                    for target in wd_targets {
+                        if target.only.as_ref().is_some_and(|only| only != name) {
+                            continue;
+                        }
+                        let path = target.dir.join(name);
+                        if ignored(&target.ignore, &path) {
+                            continue;
+                        }
+                        /* new subdir of a recursively watched dir */
+                        if target.only.is_none()
+                            && event.mask.contains(AddWatchFlags::IN_ISDIR)
+                            && event.mask.intersects(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO)
+                        {
+                            new_dirs.push((target.app_name.clone(), path, target.ignore.clone()));
+                            continue;
+                        }
+                        if debounces.contains_key(&target.app_name) {
+                            pending.insert(target.app_name.clone(), (time::Instant::now(), path));
+                        }
+                    }
+                    for (app_name, path, ignore) in new_dirs {
+                        add_dir(inotify, &mut targets, &app_name, &path, &ignore);
+                    }
+                }
+
+                let settled: Vec<(String, PathBuf)> = pending
+                    .iter()
+                    .filter(|(app_name, (last_change, _))| {
+                        debounces
+                            .get(*app_name)
+                            .is_none_or(|debounce| last_change.elapsed() >= *debounce)
+                    })
+                    .map(|(app_name, (_, path))| (app_name.clone(), path.clone()))
+                    .collect();
+                for (app_name, path) in settled {
+                    pending.remove(&app_name);
+                    /* turned off meanwhile, or not running */
+                    let running = handler
+                        .processes_table
+                        .lock()
+                        .unwrap()
+                        .iter()
+                        .any(|process_child| process_child.name == app_name);
+                    if !debounces.contains_key(&app_name) || !running {
+                        continue;
+                    }
+                    println!(
+                        "[pm][Info] {app_name} restarting, {} changed",
+                        path.display()
+                    );
+                    println!("{}", handler.restart_app_by_name(&app_name, ExitTrigger::Watch));
+                }
+            }
+        });
+    }
+
+    /**
+     * exec the binary on disk (possibly a new version) as `daemon --upgrade <fd>`,
+     * pid stays the same, so apps are still our children, and they are adopted
+     * with their pipes and sockets from the state file, see `StateFile::build_for_upgrade`,
+     * only returns on failure
+     */
+    fn upgrade(self: &Self) -> io::Error {
+        let Some(listener_fd) = *self.listener_fd.lock().unwrap() else {
+            return io::Error::other("not listening");
+        };
+        /* replaced binary shows as "/path/pm (deleted)" */
+        let exe = match env::current_exe() {
+            Ok(exe) => PathBuf::from(
+                exe.to_string_lossy()
+                    .trim_end_matches(" (deleted)")
+                    .to_string(),
+            ),
+            Err(e) => return e,
+        };
+        /* held until exec, so nothing changes after the state is written */
+        let config_lock = self.config.lock().unwrap();
+        let processes_table_lock = self.processes_table.lock().unwrap();
+        let state = StateFile::build_for_upgrade(&config_lock, &processes_table_lock);
+        if let Err(e) = state.write().and_then(|()| set_cloexec(listener_fd, false)) {
+            return e;
+        }
+        println!("[pm][Info] upgrading daemon, exec {}", exe.display());
+        let e = Command::new(&exe)
+            .args(["daemon", "--upgrade", &listener_fd.to_string()])
+            .arg(&config_lock.config_filepath)
+            .exec();
+
+        /* still here, don't leak them into apps spawned later */
+        let _ = set_cloexec(listener_fd, true);
+        for entry in &state.processes {
+            for fd in [entry.stdin_fd, entry.pty_fd].into_iter().flatten() {
+                let _ = set_cloexec(fd, true);
+            }
+        }
+        for fd in state.apps.iter().flat_map(|app| &app.listen_fds) {
+            let _ = set_cloexec(*fd, true);
+        }
+        StateFile::save(&processes_table_lock);
+        e
+    }
+
+    fn start_watchdog_loop(self: &Self) {
+        let handler = self.clone();
+        thread::spawn(move || {
+            /* pid -> (its cgroup, oom_kill count of the cgroup at last tick) */
+            let mut oom_kills: HashMap<u32, (PathBuf, u64)> = HashMap::new();
+            loop {
+                thread::sleep(Duration::from_secs(3));
+                /* always lock config before processes_table, same as others, or deadlock */
+                let mut config_lock = handler.config.lock().unwrap();
+                let mut processes_table_lock = handler.processes_table.lock().unwrap();
+                /* (name, instance) of oneshot/cron runs which are done, removed after the loop */
+                let mut finished_runs = vec![];
+                let mut table_changed = false;
+                for process_child in processes_table_lock.iter_mut() {
+                    let exited = process_child.try_exit();
+                    /* a paused app can't ping, and is not hung */
+                    if exited.is_none()
+                        && process_child.paused.is_none()
+                        && let Some(watchdog_sec) = config_lock
+                            .find_config(&process_child.name)
+                            .filter(|app_config| app_config.notify.unwrap_or(false))
+                            .and_then(|app_config| app_config.watchdog_sec)
+                    {
+                        handler.check_watchdog_deadline(process_child, watchdog_sec);
+                    }
+                    if let Some(exit_status) = exited {
+                        handler
+                            .notify_states
+                            .lock()
+                            .unwrap()
+                            .remove(&process_child.child.id());
+                        if let Some(app_config) = config_lock.find_config(&process_child.name) {
+                            /* a SIGKILL is taken as by OOM killer if it killed anything in its cgroup since last tick */
+                            let oom_killed = oom_kills
+                                .remove(&process_child.child.id())
+                                .and_then(|(dir, last)| Some(cgroup_oom_kills(&dir)? > last));
+                            let trigger = match process_child.killed_for {
+                                Some(trigger) => trigger,
+                                None if !app_config.app_type.is_service() => ExitTrigger::Finished,
+                                None if exit_status.signal() == Some(libc::SIGKILL) => {
+                                    match oom_killed {
+                                        Some(true) => ExitTrigger::MemoryLimit,
+                                        Some(false) => ExitTrigger::Crash,
+                                        None => ExitTrigger::Unknown,
+                                    }
+                                }
+                                None => ExitTrigger::Crash,
+                            };
+                            handler.exit_history.lock().unwrap().record(
+                                &process_child.name,
+                                ExitRecord::new(process_child, exit_status, trigger),
+                            );
+                            if !app_config.app_type.is_service() {
+                                let record = RunRecord {
+                                    started_at: process_child.started_at,
+                                    duration: process_child.started_at.elapsed().unwrap_or_default(),
+                                    exit_status,
+                                };
+                                println!("[pm][Info] {} finished: {record}", process_child.name);
+                                app_config.last_run = Some(record);
+                                finished_runs.push((process_child.name.clone(), process_child.instance));
+                                continue;
+                            }
+
+                            /* exited */
+                            app_config.exit_count += 1;
+                            let policy = app_config.restart.unwrap_or_default();
+                            if !policy.should_restart(&exit_status) {
+                                println!(
+                                    "[pm][Info] {} exited ({exit_status}), not restarted, restart = {policy:?}",
+                                    process_child.name
+                                );
+                                app_config.stopped = true;
+                                finished_runs.push((process_child.name.clone(), process_child.instance));
+                                continue;
+                            }
+                            println!(
+                                "[pm][Info] {} exited! try to restart...",
+                                process_child.name
+                            );
+                            *app_config.restarts.entry("crash".into()).or_default() += 1;
+                            match Self::spawn_app(app_config, process_child.instance) {
+                                Ok(new_process_child) => {
+                                    *process_child = new_process_child;
+                                    table_changed = true;
+                                }
+                                Err(e) => eprintln!(
+                                    "[pm][Error] failed to restart {}: {e}",
+                                    process_child.name
+                                ),
+                            }
+                        } else {
+                            eprintln!(
+                                "[pm][Error] why {} is in the process table but not in app configs?",
+                                process_child.name
+                            );
+                        }
+                    }
+                }
+                processes_table_lock
+                    .retain(|p| !finished_runs.contains(&(p.name.clone(), p.instance)));
+                if table_changed || !finished_runs.is_empty() {
+                    StateFile::save(&processes_table_lock);
+                }
+                oom_kills = processes_table_lock
+                    .iter()
+                    .filter_map(|p| {
+                        let pid = p.child.id();
+                        let dir = match oom_kills.remove(&pid) {
+                            Some((dir, _)) => dir,
+                            None => proc_cgroup_dir(&pid.to_string())?,
+                        };
+                        let count = cgroup_oom_kills(&dir)?;
+                        Some((pid, (dir, count)))
+                    })
+                    .collect();
+
+                /* queued runs start once all instances of last run finished */
+                for app_config in config_lock.apps.iter_mut() {
+                    if app_config.run_queued
+                        && !processes_table_lock.iter().any(|p| p.name == app_config.name)
+                    {
+                        app_config.run_queued = false;
+                        println!("[pm][Info] {} start queued run", app_config.name);
+                        drop(processes_table_lock);
+                        println!(
+                            "{}",
+                            handler.spawn_into_table(app_config).unwrap_or_else(|e| e)
+                        );
+                        processes_table_lock = handler.processes_table.lock().unwrap();
+                    }
+                }
+            }
+        });
+    }
+
+    /**
+     * restart apps with `cron_restart` when the time comes,
+     * through the same path as `restart` command,
+     * and run `type = "cron"` apps on their `schedule`
+     */
+    fn start_cron_loop(self: &Self) {
+        let handler = self.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(Duration::from_secs(1));
+                let now = time::SystemTime::now();
+                /* keep `next` up to date with `expr`, true if it's time */
+                fn is_due(
+                    expr: Option<&str>,
+                    next: &mut Option<time::SystemTime>,
+                    now: time::SystemTime,
+                ) -> bool {
+                    let Some(schedule) = expr.and_then(|e| CronSchedule::from_str(e).ok()) else {
+                        *next = None;
+                        return false;
+                    };
+                    match *next {
+                        Some(t) if t <= now => {
+                            *next = schedule.next_after(now);
+                            true
+                        }
+                        Some(_) => false,
+                        None => {
+                            *next = schedule.next_after(now);
+                            false
+                        }
+                    }
+                }
+
+                let (due_restarts, due_runs) = {
+                    let mut config_lock = handler.config.lock().unwrap();
+                    let mut due_restarts = vec![];
+                    let mut due_runs = vec![];
+                    for app_config in config_lock.apps.iter_mut() {
+                        let enabled = !app_config.stopped;
+                        let app_type = app_config.app_type;
+                        if is_due(
+                            app_config
+                                .cron_restart
+                                .as_deref()
+                                .filter(|_| enabled && app_type == AppType::Service),
+                            &mut app_config.next_cron_restart,
+                            now,
+                        ) {
+                            due_restarts.push(app_config.name.clone());
+                        }
+                        if is_due(
+                            app_config
+                                .schedule
+                                .as_deref()
+                                .filter(|_| enabled && app_type == AppType::Cron),
+                            &mut app_config.next_scheduled_run,
+                            now,
+                        ) {
+                            due_runs.push(app_config.name.clone());
+                        }
+                    }
+                    (due_restarts, due_runs)
+                };
+
+                for app_name in due_restarts {
+                    println!("[pm][Info] scheduled restart of {app_name}");
+                    println!("{}", handler.restart_app_by_name(&app_name, ExitTrigger::CronRestart));
+                }
+                for app_name in due_runs {
+                    println!("[pm][Info] scheduled run of {app_name}");
+                    println!("{}", handler.try_run_app_by_name(&app_name).unwrap_or_else(|e| e));
+                }
+            }
+        });
+    }
+
+
+    /**
+     * start `on_demand` apps when any of their sockets becomes readable,
+     * after they stop, it waits for connections again
+     */
+    fn start_on_demand_loop(self: &Self) {
+        let handler = self.clone();
+        thread::spawn(move || {
+            loop {
+                /* (app name, fd) of on demand apps not running yet */
+                let watched: Vec<(String, RawFd)> = {
+                    let config_lock = handler.config.lock().unwrap();
+                    let table_lock = handler.processes_table.lock().unwrap();
+                    config_lock
+                        .apps
+                        .iter()
+                        .filter(|app| !app.stopped && app.on_demand.unwrap_or(false))
+                        .filter(|app| !table_lock.iter().any(|p| p.name == app.name))
+                        .flat_map(|app| {
+                            app.listen_fds
+                                .iter()
+                                .map(|fd| (app.name.clone(), fd.as_raw_fd()))
+                        })
+                        .collect()
+                };
+                if watched.is_empty() {
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+
+                let mut pollfds: Vec<libc::pollfd> = watched
+                    .iter()
+                    .map(|(_, fd)| libc::pollfd {
+                        fd: *fd,
+                        events: libc::POLLIN,
+                        revents: 0,
+                    })
+                    .collect();
+                /* timeout, so newly enabled/stopped apps are picked up */
+                let n = unsafe {
+                    libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, 1000)
+                };
+                if n <= 0 {
+                    continue;
+                }
+                let mut app_names: Vec<&String> = pollfds
+                    .iter()
+                    .zip(&watched)
+                    .filter(|(pollfd, _)| pollfd.revents & libc::POLLIN != 0)
+                    .map(|(_, (name, _))| name)
+                    .collect();
+                app_names.dedup();
+                for app_name in app_names {
+                    println!("[pm][Info] {app_name} got a connection, starting on demand");
+                    println!(
+                        "{}",
+                        handler.try_start_app_by_name(app_name).unwrap_or_else(|e| e)
+                    );
+                }
+            }
+        });
+    }
+
+    /**
+     * receive sd_notify messages of all `notify` apps
+     */
+    fn start_notify_loop(self: &Self) {
+        let handler = self.clone();
+        thread::spawn(move || {
+            loop {
+                let watched: Vec<(String, RawFd)> = handler
+                    .config
+                    .lock()
+                    .unwrap()
+                    .apps
+                    .iter()
+                    .filter_map(|app| {
+                        let fd = app.notify_socket.as_ref()?.as_raw_fd();
+                        Some((app.name.clone(), fd))
+                    })
+                    .collect();
+                if watched.is_empty() {
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+
+                let mut pollfds: Vec<libc::pollfd> = watched
+                    .iter()
+                    .map(|(_, fd)| libc::pollfd {
+                        fd: *fd,
+                        events: libc::POLLIN,
+                        revents: 0,
+                    })
+                    .collect();
+                let n = unsafe {
+                    libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, 1000)
+                };
+                if n <= 0 {
+                    continue;
+                }
+                for (pollfd, (app_name, fd)) in pollfds.iter().zip(&watched) {
+                    if pollfd.revents & libc::POLLIN == 0 {
+                        continue;
+                    }
+                    match recv_with_pid(*fd) {
+                        Ok((message, sender_pid)) => {
+                            handler.handle_notify_message(app_name, sender_pid, &message)
+                        }
+                        Err(e) => eprintln!("[pm][Error] {app_name} notify socket: {e}"),
+                    }
+                }
+            }
+        });
+    }
+
+    /**
+     * message is attributed to the instance with the same pid (or MAINPID),
+     * or the only instance, e.g. sent by a helper process of the app
+     */
+    fn handle_notify_message(self: &Self, app_name: &str, sender_pid: Option<u32>, message: &str) {
+        let child_pids: Vec<u32> = self
+            .processes_table
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|process_child| process_child.name == app_name)
+            .map(|process_child| process_child.child.id())
+            .collect();
+        let mut notify_states = self.notify_states.lock().unwrap();
+        let pid = sender_pid.and_then(|sender_pid| {
+            child_pids.iter().copied().find(|pid| {
+                *pid == sender_pid
+                    || notify_states.get(pid).and_then(|state| state.main_pid) == Some(sender_pid)
+            })
+        });
+        /* a child being started by `reload` is not in the table yet, but registered */
+        let pid = match (pid, sender_pid, child_pids.as_slice()) {
+            (Some(pid), _, _) => pid,
+            (None, Some(sender_pid), _) if notify_states.contains_key(&sender_pid) => sender_pid,
+            (None, Some(sender_pid), []) => sender_pid,
+            (None, _, [only]) => *only,
+            _ => return,
+        };
+
+        let state = notify_states.entry(pid).or_default();
+        for line in message.lines() {
+            match line.split_once('=') {
+                Some(("READY", "1")) => {
+                    state.ready = true;
+                    state.reloading = false;
+                    println!("[pm][Info] {app_name} (pid {pid}) is ready");
+                }
+                Some(("RELOADING", "1")) => state.reloading = true,
+                Some(("STOPPING", "1")) => state.stopping = true,
+                Some(("STATUS", status)) => state.status = Some(status.to_string()),
+                Some(("MAINPID", main_pid)) => state.main_pid = main_pid.parse().ok(),
+                Some(("WATCHDOG", "1")) => state.last_watchdog = Some(time::Instant::now()),
+                _ => {}
+            }
+        }
+    }
+
+    /**
+     * abort the child if no WATCHDOG=1 within `watchdog_sec`,
+     * counting from last ping, or from spawn if never pinged
+     */
+    fn check_watchdog_deadline(self: &Self, process_child: &mut ProcessChild, watchdog_sec: u64) {
+        let pid = process_child.child.id();
+        let last_alive = self
+            .notify_states
+            .lock()
+            .unwrap()
+            .get(&pid)
+            .and_then(|state| state.last_watchdog);
+        let missed = match last_alive {
+            Some(last) => last.elapsed() > Duration::from_secs(watchdog_sec),
+            None => {
+                process_child.started_at.elapsed().unwrap_or_default()
+                    > Duration::from_secs(watchdog_sec)
+            }
+        };
+        if missed && let Ok(None) = process_child.child.try_wait() {
+            eprintln!(
+                "[pm][Warn] {} (pid {pid}) missed watchdog deadline, aborting",
+                process_child.name
+            );
+            process_child.killed_for = Some(ExitTrigger::HealthCheck);
+            let _ = Self::nice_kill_process(
+                &mut process_child.child,
+                nix::sys::signal::Signal::SIGABRT,
+                time::Duration::from_millis(1000),
+            );
+        }
+    }
+
+    /**
+     * for `ls`, e.g. "Starting", "Running, processing 3 jobs"
+     */
+    fn describe_notify_state(self: &Self, app_config: &AppConfig, pid: u32, status: &str) -> String {
+        if !app_config.notify.unwrap_or(false) || status != "Running" {
+            return status.to_string();
+        }
+        let notify_states = self.notify_states.lock().unwrap();
+        let state = notify_states.get(&pid);
+        let phase = match state {
+            Some(state) if state.stopping => "Stopping",
+            Some(state) if state.reloading => "Reloading",
+            Some(state) if state.ready => "Running",
+            _ => "Starting",
+        };
+        match state.and_then(|state| state.status.as_ref()) {
+            Some(text) => format!("{phase}, {text}"),
+            None => phase.to_string(),
+        }
+    }
+
+    fn start_all_apps(self: &Self) {
+        /* not locked while starting, hooks may take a while */
+        let app_names: Vec<String> = {
+            let mut config = self.config.lock().unwrap();
+            let mut app_names = vec![];
+            for app_config in config.apps.iter_mut() {
+                if !app_config.enabled {
+                    app_config.stopped = true;
+                }
+                if app_config.stopped {
+                    println!("[pm][Info] {} is not started, disabled or stopped", app_config.name);
+                    continue;
+                }
+                if app_config.on_demand.unwrap_or(false) && !app_config.listen_fds.is_empty() {
+                    println!("[pm][Info] {} waits for the first connection", app_config.name);
+                    continue;
+                }
+                app_names.push(app_config.name.clone());
+            }
+            app_names
+        };
+        for app_name in app_names {
+            let res = self.try_start_app_by_name(&app_name);
+            println!("{}", res.as_ref().unwrap_or_else(|e| e));
+            match res {
+                Ok(o) => {
+                    println!("{o}");
+                }
+                Err(e) => println!("[pm][Error] {e}"),
+            }
+        }
+    }
+    fn stop_all_apps(self: &Self) {
+        let app_names: Vec<String> = {
+            let processes_table = self.processes_table.lock().unwrap();
+            let app_names = processes_table.iter().map(|p| p.name.clone()).collect();
+            app_names
+        };
+
+        for app_name in app_names {
+            let _ = self.try_stop_app_by_name(&app_name, ExitTrigger::Stop);
+        }
+    }
+
+
+    /**
+     * stop then start, results of both steps are returned line by line,
+     * `trigger` is recorded for the exits, and counted for `show` if it was running
+     */
+    fn restart_app_by_name(self: &Self, app_name: &str, trigger: ExitTrigger) -> String {
+        let stop_result = match self.try_stop_app_by_name(app_name, trigger) {
+            Ok(result) => {
+                let reason = match trigger {
+                    ExitTrigger::CronRestart => "cron_restart",
+                    ExitTrigger::Watch => "watch",
+                    _ => "restart",
+                };
+                self.count_restart(app_name, reason);
+                result
+            }
+            Err(e) => e.to_string(),
+        };
+        let start_result = self.try_start_app_by_name(app_name).unwrap_or_else(|e| e);
+        format!("{stop_result}\n{start_result}")
+    }
+
+    fn count_restart(self: &Self, app_name: &str, reason: &str) {
+        if let Some(app_config) = self.config.lock().unwrap().find_config(app_name) {
+            *app_config.restarts.entry(reason.to_string()).or_default() += 1;
+        }
+    }
+
+    /**
+     * `start` command, runtime only, pm.toml is not touched
+     */
+    fn start_app_by_name(self: &Self, app_name: &str) -> Result<String, String> {
+        match self.config.lock().unwrap().find_config(app_name) {
+            Some(app_config) => app_config.stopped = false,
+            None => return Err(format!("[pm][Warn] {app_name} not found")),
+        }
+        self.try_start_app_by_name(app_name)
+    }
+
+    /**
+     * `stop` command, runtime only, it's not restarted by anything until `start`
+     */
+    fn stop_app_by_name(self: &Self, app_name: &str) -> Result<String, String> {
+        match self.config.lock().unwrap().find_config(app_name) {
+            Some(app_config) => app_config.stopped = true,
+            None => return Err(format!("[pm][Warn] {app_name} not found")),
+        }
+        self.try_stop_app_by_name(app_name, ExitTrigger::Stop).map_err(str::to_string)
+    }
+
+    /**
+     * `enable`/`disable`, autostart with daemon, saved to pm.toml,
+     * whether it's running now is not changed
+     */
+    fn try_enable_app_by_name(self: &Self, app_name: &str, enabled: bool) -> String {
+        let mut config_lock = self.config.lock().unwrap();
+        if config_lock.find_config(app_name).is_none() {
+            return format!("[pm][Warn] {app_name} not found");
+        }
+        config_lock.enable(app_name, enabled);
+        match enabled {
+            true => format!("[pm][Info] {app_name} enabled, it starts with daemon, `start` to start it now"),
+            false => format!("[pm][Info] {app_name} disabled, it won't start with daemon, `stop` to stop it now"),
+        }
+    }
+
+    /**
+     * `show`, config and runtime details of an app, as tables
+     */
+    fn show_app_by_name(self: &Self, app_name: &str) -> Result<String, String> {
+        use tabled::{builder::Builder, settings::Style};
+
+        let mut config_table = Builder::new();
+        let mut runtime_table = Builder::new();
+        let (log_files, executable) = {
+            let config_lock = self.config.lock().unwrap();
+            let Some(app_config) = config_lock.apps.iter().find(|app| app.name == app_name) else {
+                return Err(format!("[pm][Warn] {app_name} not found"));
+            };
+            config_table.push_record([app_name, ""]);
+            for (key, value) in app_config.fields() {
+                config_table.push_record([key.to_string(), value]);
+            }
+
+            let restarts: u32 = app_config.restarts.values().sum();
+            let reasons: Vec<String> = app_config
+                .restarts
+                .iter()
+                .map(|(reason, count)| format!("{reason} {count}"))
+                .collect();
+            runtime_table.push_record([
+                "restarts".to_string(),
+                match reasons.is_empty() {
+                    true => restarts.to_string(),
+                    false => format!("{restarts} ({})", reasons.join(", ")),
+                },
+            ]);
+            runtime_table.push_record([
+                "last exit".to_string(),
+                self.exit_history
+                    .lock()
+                    .unwrap()
+                    .last(app_name)
+                    .map(|record| {
+                        format!(
+                            "{}, {}, {}",
+                            format_local_time(record.time()),
+                            record
+                                .exit_status()
+                                .map(describe_exit_status)
+                                .unwrap_or("unknown".to_string()),
+                            record.trigger
+                        )
+                    })
+                    .unwrap_or("-".to_string()),
+            ]);
+            let log_files: Vec<PathBuf> = (0..app_config.instances())
+                .map(|instance| app_config.log_file(instance))
+                .collect();
+            (log_files, resolve_executable(&app_config.cmd, &app_config.cwd))
+        };
+
+        /* (instance, pid, started_at, paused) of those alive */
+        let mut instances: Vec<(u32, u32, time::SystemTime, bool)> = self
+            .processes_table
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .filter(|process_child| process_child.name == app_name)
+            .filter_map(|process_child| match process_child.child.try_wait() {
+                Ok(None) => Some((
+                    process_child.instance,
+                    process_child.child.id(),
+                    process_child.started_at,
+                    process_child.paused.is_some(),
+                )),
+                _ => None,
+            })
+            .collect();
+        instances.sort_by_key(|(instance, ..)| *instance);
+        if instances.is_empty() {
+            runtime_table.push_record(["status", "not running"]);
+            runtime_table.push_record([
+                "executable".to_string(),
+                executable.map(|path| path.display().to_string()).unwrap_or("not found".into()),
+            ]);
+        }
+
+        /* cpu usage over a short while, not since start */
+        let sample = Duration::from_millis(200);
+        let cpu_before: Vec<Option<u64>> =
+            instances.iter().map(|(_, pid, ..)| proc_cpu_ticks(*pid)).collect();
+        if !instances.is_empty() {
+            thread::sleep(sample);
+        }
+        let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+        for ((instance, pid, started_at, paused), cpu_before) in instances.iter().zip(cpu_before) {
+            let key = |key: &str| format!("#{instance} {key}");
+            let pgid = nix::unistd::getpgid(Some(nix::unistd::Pid::from_raw(*pid as i32)))
+                .map(|pgid| pgid.to_string())
+                .unwrap_or("?".to_string());
+            runtime_table.push_record([
+                key("status"),
+                match paused {
+                    true => "paused".to_string(),
+                    false => "running".to_string(),
+                },
+            ]);
+            runtime_table.push_record([key("pid / pgid"), format!("{pid} / {pgid}")]);
+            runtime_table.push_record([
+                key("started"),
+                format!(
+                    "{}, up {}",
+                    format_local_time(*started_at),
+                    format_duration(started_at.elapsed().unwrap_or_default())
+                ),
+            ]);
+            runtime_table.push_record([
+                key("executable"),
+                fs::read_link(format!("/proc/{pid}/exe"))
+                    .map(|path| path.display().to_string())
+                    .unwrap_or("?".to_string()),
+            ]);
+            runtime_table.push_record([
+                key("cmdline"),
+                proc_cmdline(*pid).map(|args| args.join(" ")).unwrap_or("?".to_string()),
+            ]);
+            let cpu = match (cpu_before, proc_cpu_ticks(*pid)) {
+                (Some(before), Some(after)) => format!(
+                    "{:.1}%",
+                    (after - before) as f64 / ticks_per_sec / sample.as_secs_f64() * 100.0
+                ),
+                _ => "?".to_string(),
+            };
+            let memory = proc_rss_kb(*pid)
+                .map(|kb| format_size(kb * 1024))
+                .unwrap_or("?".to_string());
+            runtime_table.push_record([key("cpu / memory"), format!("{cpu} / {memory}")]);
+        }
+        for log_file in log_files {
+            let size = fs::metadata(&log_file)
+                .map(|metadata| format_size(metadata.len()))
+                .unwrap_or("missing".to_string());
+            runtime_table.push_record(["log".to_string(), format!("{} ({size})", log_file.display())]);
+        }
+
+        let mut config_table = config_table.build();
+        config_table.with(Style::sharp());
+        let mut runtime_table = runtime_table.build();
+        runtime_table.with(Style::sharp());
+        Ok(format!("{config_table}\n{runtime_table}"))
+    }
+
+    /**
+     * replace an app with its edited toml from `pm edit`, stopped and started again if running,
+     * sockets are kept if `listen` and `notify` didn't change
+     */
+    fn try_edit_app_by_name(self: &Self, app_name: &str, snippet: &str) -> Result<String, String> {
+        let mut new_config: AppConfig =
+            toml::from_str(snippet).map_err(|e| format!("[pm][Error] {e}"))?;
+        new_config.validate().map_err(|e| format!("[pm][Error] {e}"))?;
+        if let Some(ns) = &new_config.namespace {
+            new_config.name = format!("{ns}/{}", new_config.name);
+        }
+        if new_config.name != app_name {
+            return Err(format!(
+                "[pm][Error] name and namespace can't be changed by edit, `add` {} as a new app instead",
+                new_config.name
+            ));
+        }
+        {
+            let mut config_lock = self.config.lock().unwrap();
+            let Some(old_config) = config_lock.find_config(app_name) else {
+                return Err(format!("[pm][Warn] {app_name} not found"));
+            };
+            if toml::to_string(old_config).ok() == toml::to_string(&new_config).ok() {
+                return Ok(format!("[pm][Info] {app_name} unchanged"));
+            }
+        }
+
+        let running = !self.instance_pids(app_name).is_empty();
+        let mut results = vec![];
+        if running && let Ok(result) = self.try_stop_app_by_name(app_name, ExitTrigger::Stop) {
+            results.push(result);
+        }
+        {
+            let mut config_lock = self.config.lock().unwrap();
+            let Some(old_config) = config_lock.find_config(app_name) else {
+                return Err(format!("[pm][Warn] {app_name} was removed meanwhile"));
+            };
+            new_config.exit_count = old_config.exit_count;
+            new_config.restarts = std::mem::take(&mut old_config.restarts);
+            new_config.stopped = old_config.stopped;
+            new_config.watch_off = old_config.watch_off;
+            new_config.last_run = old_config.last_run.take();
+            /* closed before binding the new ones, which may use the same address */
+            let listen_fds = std::mem::take(&mut old_config.listen_fds);
+            match new_config.listen == old_config.listen {
+                true => new_config.listen_fds = listen_fds,
+                false => drop(listen_fds),
+            }
+            let notify_socket = old_config.notify_socket.take();
+            match new_config.notify == old_config.notify {
+                true => new_config.notify_socket = notify_socket,
+                false => drop(notify_socket),
+            }
+            new_config.bind_listen_sockets().unwrap_or_else(|e| {
+                results.push(format!("[pm][Error] {app_name} failed to bind listen sockets: {e}"))
+            });
+            new_config.bind_notify_socket().unwrap_or_else(|e| {
+                results.push(format!("[pm][Error] {app_name} failed to bind notify socket: {e}"))
+            });
+            *old_config = new_config;
+            config_lock
+                .save()
+                .unwrap_or_else(|_| eprintln!("[pm][Error] save config failed"));
+        }
+        results.push(format!("[pm][Info] {app_name} updated"));
+        if running {
+            results.push(self.try_start_app_by_name(app_name).unwrap_or_else(|e| e));
+        }
+        Ok(results.join("\n"))
+    }
+
+    /**
+     * stop it as `stop` does (kill_signal and hooks), then forget it,
+     * logs are kept unless `delete_logs`
+     */
+    fn try_remove_app_by_name(self: &Self, app_name: &str, delete_logs: bool) -> Result<String, String> {
+        if self.config.lock().unwrap().find_config(app_name).is_none() {
+            return Err(format!("[pm][Warn] {app_name} not found"));
+        }
+        let mut results = vec![];
+        if let Ok(result) = self.try_stop_app_by_name(app_name, ExitTrigger::Stop) {
+            results.push(result);
+        }
+
+        let removed = {
+            let mut config_lock = self.config.lock().unwrap();
+            let Some(removed) = config_lock.remove_config(app_name) else {
+                return Err(format!("[pm][Warn] {app_name} was removed meanwhile"));
+            };
+            /* started by cron or on demand while stopping */
+            let mut table_lock = self.processes_table.lock().unwrap();
+            let (mut late, rest): (Vec<ProcessChild>, Vec<ProcessChild>) = table_lock
+                .drain(..)
+                .partition(|process_child| process_child.name == app_name);
+            *table_lock = rest;
+            StateFile::save(&table_lock);
+            for process_child in late.iter_mut() {
+                process_child.resume_if_paused();
+                let _ = process_child.child.kill();
+                let _ = process_child.child.wait();
+            }
+            removed
+        };
+        self.exit_history.lock().unwrap().forget(app_name);
+        if removed.notify_socket.is_some() {
+            let _ = fs::remove_file(removed.notify_socket_path());
+        }
+        for listen in &removed.listen {
+            if let Some(path) = &listen.unix {
+                let _ = fs::remove_file(path);
+            }
+        }
+
+        if delete_logs {
+            /* <name>.log and <name>-<instance>.log, also of instances scaled away */
+            let logdir = removed.log_file(0).parent().map(Path::to_path_buf).unwrap_or_default();
+            let other_apps: Vec<String> = self
+                .config
+                .lock()
+                .unwrap()
+                .apps
+                .iter()
+                .map(AppConfig::file_stem)
+                .collect();
+            let app_stem = removed.file_stem();
+            let is_log = |file_name: &str| {
+                let Some(stem) = file_name.strip_suffix(".log") else {
+                    return false;
+                };
+                /* app "x-2" is not instance 2 of app "x" */
+                if other_apps.iter().any(|other| other == stem) {
+                    return false;
+                }
+                stem == app_stem
+                    || stem
+                        .strip_prefix(&format!("{app_stem}-"))
+                        .is_some_and(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+            };
+            for entry in fs::read_dir(&logdir).into_iter().flatten().flatten() {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                if !is_log(&file_name) {
+                    continue;
+                }
+                results.push(match fs::remove_file(entry.path()) {
+                    Ok(()) => format!("[pm][Info] deleted {}", entry.path().display()),
+                    Err(e) => format!("[pm][Warn] failed to delete {}: {e}", entry.path().display()),
+                });
+            }
+        }
+        results.push(format!("[pm][Info] {app_name} removed"));
+        Ok(results.join("\n"))
+    }
+
+    /**
+     * `try_start_app` with pre_start and post_start hooks,
+     * hooks run only if something will be spawned, and config is not locked meanwhile
+     */
+    fn try_start_app_by_name(self: &Self, app_name: &str) -> Result<String, String> {
+        let hooks = {
+            let mut config_lock = self.config.lock().unwrap();
+            let Some(app_config) = config_lock.find_config(&app_name) else {
+                return Err(format!("The App name {app_name} can't be found in config"));
+            };
+            let started = self
+                .processes_table
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|process_child| process_child.name == app_name)
+                .count() as u32;
+            let will_spawn = !app_config.stopped
+                && app_config.app_type != AppType::Cron
+                && started < app_config.instances();
+            match will_spawn {
+                true => app_config.hooks.clone(),
+                false => return self.try_start_app(app_config),
+            }
+        };
+
+        hooks
+            .run(app_name, "pre_start", &[])
+            .map_err(|e| format!("[pm][Error] {app_name} was not started, {e}"))?;
+
+        let result = {
+            let mut config_lock = self.config.lock().unwrap();
+            let app_config = config_lock.find_config(&app_name);
+            if let Some(app_config) = app_config {
+                self.try_start_app(app_config)
+                // let _ = try_start_process(app_config, processes_table.clone());
+            } else {
+                Err(format!("The App name {app_name} can't be found in config"))
+            }
+        }?;
+
+        let pids = self.pids_of(app_name);
+        match hooks.run(app_name, "post_start", &[("PM_APP_PID", pids)]) {
+            Ok(()) => Ok(result),
+            Err(e) => Ok(format!("{result}\n[pm][Warn] {app_name}: {e}")),
+        }
+    }
+
+    /**
+     * pids of all instances, space separated, for hooks
+     */
+    fn pids_of(self: &Self, app_name: &str) -> String {
+        self.instance_pids(app_name)
+            .iter()
+            .map(|pid| pid.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /**
+     * pids of all instances, ordered by instance id
+     */
+    fn instance_pids(self: &Self, app_name: &str) -> Vec<u32> {
+        let mut instances: Vec<(u32, u32)> = self
+            .processes_table
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|process_child| process_child.name == app_name)
+            .map(|process_child| (process_child.instance, process_child.child.id()))
+            .collect();
+        instances.sort();
+        instances.into_iter().map(|(_, pid)| pid).collect()
+    }
+
+    /**
+     * this do some checks for you:
+     * 1. the app is enabled
+     * 2. the app is not started (i.e. not in the table)
+     */
+    fn try_start_app(self: &Self, app_config: &AppConfig) -> Result<String, String> {
+        let app_name = &app_config.name;
+        // let mut config_lock = config.lock().unwrap();
+        // let app_config = config_lock.find_config(&app_name);
+
+
+        // if let Some(app_config) = app_config {
+        if !app_config.stopped {
+            if app_config.app_type == AppType::Cron {
+                return Ok(format!(
+                    "[pm][Info] {app_name} is a cron job, it runs on schedule or by `run`"
+                ));
+            }
+            self.spawn_into_table(app_config)
+        } else {
+            Err(format!("[pm][Info] {app_name} is stopped, `start` it first"))
+        }
+        // } else {
+        //     Err("The App name can't be found in config")
+        // }
+    }
+
+    /**
+     * spawn instances which are not started yet and add them to table
+     */
+    fn spawn_into_table(self: &Self, app_config: &AppConfig) -> Result<String, String> {
+        let app_name = &app_config.name;
+        let started: Vec<u32> = self
+            .processes_table
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|process_child| &process_child.name == app_name)
+            .map(|process_child| process_child.instance)
+            .collect();
+        let missing: Vec<u32> = (0..app_config.instances())
+            .filter(|instance| !started.contains(instance))
+            .collect();
+
+        if missing.is_empty() {
+            return Err(format!("[pm][Info] {app_name} has already been started"));
+        }
+        let mut results = vec![];
+        let mut failed = false;
+        for instance in missing {
+            /* keep the old message for single instance apps */
+            let display_name = match app_config.instances() {
+                1 => app_name.to_string(),
+                _ => format!("{app_name} #{instance}"),
+            };
+            // let _ = writeln!(stream, "Let's spawn");
+            match Self::spawn_app(app_config, instance) {
+                Ok(process_child) => {
+                    let mut table_lock = self.processes_table.lock().unwrap();
+                    table_lock.push(process_child);
+                    StateFile::save(&table_lock);
+                    results.push(format!("[pm][Info] {display_name} was spawned successfully"));
+                }
+                Err(e) => {
+                    failed = true;
+                    results.push(format!("[pm][Error] {display_name} was failed to spawn: {e}"));
+                }
+            }
+        }
+        match failed {
+            false => Ok(results.join("\n")),
+            true => Err(results.join("\n")),
+        }
+    }
+
+    /**
+     * change number of instances, save it to config,
+     * then spawn missing instances or stop the extra ones (highest id first)
+     */
+    fn try_scale_app_by_name(self: &Self, app_name: &str, n: u32) -> Result<String, String> {
+        let start_result = {
+            let mut config_lock = self.config.lock().unwrap();
+            let app_config = config_lock
+                .find_config(app_name)
+                .ok_or(format!("The App name {app_name} can't be found in config"))?;
+            let scale_up = n > app_config.instances();
+            app_config.instances = if n == 1 { None } else { Some(n) };
+            let start_result = match scale_up && !app_config.stopped && app_config.app_type.is_service() {
+                true => Some(self.spawn_into_table(app_config).unwrap_or_else(|e| e)),
+                false => None,
+            };
+            config_lock
+                .save()
+                .unwrap_or_else(|_| eprintln!("[pm][Error] save config failed"));
+            start_result
+        };
+        let stop_result = self.try_stop_instances(app_name, n, ExitTrigger::Stop).ok();
+
+        let mut results = vec![format!("[pm][Info] {app_name} scaled to {n}")];
+        results.extend(start_result);
+        results.extend(stop_result);
+        Ok(results.join("\n"))
+    }
+
+    /**
+     * trigger a run of oneshot/cron app, respect its overlap policy
+     */
+    fn try_run_app_by_name(self: &Self, app_name: &str) -> Result<String, String> {
+        {
+            let mut config_lock = self.config.lock().unwrap();
+            let app_config = config_lock
+                .find_config(app_name)
+                .ok_or(format!("The App name {app_name} can't be found in config"))?;
+            if app_config.app_type.is_service() {
+                return Err(format!(
+                    "[pm][Warn] {app_name} is a service, use `restart` instead"
+                ));
+            }
+            if app_config.stopped {
+                return Err(format!("[pm][Info] {app_name} is stopped, `start` it first"));
+            }
+
+            let running = self
+                .processes_table
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|process_child| process_child.name == app_name);
+            if !running {
+                return self.spawn_into_table(app_config);
+            }
+            match app_config.overlap.unwrap_or_default() {
+                OverlapPolicy::Skip => {
+                    return Err(format!(
+                        "[pm][Info] {app_name} is still running, skipped"
+                    ));
+                }
+                OverlapPolicy::Queue => {
+                    app_config.run_queued = true;
+                    return Ok(format!(
+                        "[pm][Info] {app_name} is still running, queued next run"
+                    ));
+                }
+                OverlapPolicy::Replace => {}
+            }
+        }
+
+        /* replace, lock released as stopping needs it */
+        let stop_result = self
+            .try_stop_app_by_name(app_name, ExitTrigger::Stop)
+            .unwrap_or_else(|e| e.to_string());
+        let mut config_lock = self.config.lock().unwrap();
+        let app_config = config_lock
+            .find_config(app_name)
+            .ok_or(format!("The App name {app_name} can't be found in config"))?;
+        let start_result = self.spawn_into_table(app_config).unwrap_or_else(|e| e);
+        Ok(format!("{stop_result}\n{start_result}"))
+    }
+
+    /**
+     * rolling restart, replace instances one by one, each new instance must be ready
+     * before the next old one is touched, and the rollout is aborted on the first failure.
+     * new instance is started before the old one is stopped,
+     * except apps with `port`, where they would fight for the same port,
+     * so old one is stopped first and other instances keep serving meanwhile.
+     * progress is reported line by line through `report`
+     */
+    fn try_reload_app_by_name(
+        self: &Self,
+        app_name: &str,
+        report: &mut dyn FnMut(String),
+    ) -> Result<String, String> {
+        let (instances, stop_first, signal) = {
+            let mut config_lock = self.config.lock().unwrap();
+            let app_config = config_lock
+                .find_config(app_name)
+                .ok_or(format!("The App name {app_name} can't be found in config"))?;
+            if !app_config.app_type.is_service() {
+                return Err(format!("[pm][Warn] {app_name} is not a service, use `run` instead"));
+            }
+            (app_config.instances(), app_config.port.is_some(), app_config.kill_signal())
+        };
+
+        let running: Vec<u32> = self
+            .processes_table
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|process_child| process_child.name == app_name)
+            .map(|process_child| process_child.instance)
+            .collect();
+        if running.is_empty() {
+            return self.try_start_app_by_name(app_name);
+        }
+
+        for instance in 0..instances {
+            /* take old one out of table, so watchdog won't restart it */
+            let take_old = || {
+                let mut table_lock = self.processes_table.lock().unwrap();
+                let index = table_lock.iter().position(|process_child| {
+                    process_child.name == app_name && process_child.instance == instance
+                })?;
+                Some(table_lock.remove(index))
+            };
+            let stop_old = |old: Option<ProcessChild>| {
+                if let Some(mut old) = old {
+                    old.resume_if_paused();
+                    let _ = Self::nice_kill_process(
+                        &mut old.child,
+                        signal,
+                        time::Duration::from_millis(2000),
                     );
-                    {
-                        let mut config = self.config.lock().unwrap();
-                        config.add_config(AppConfig {
-                            name: params[0].to_string(),
-                            cmd: params[1].to_string(),
-                            args: params[2..].to_vec(),
-                            cwd: PathBuf::from("/"), /* TODO: implement cwd */
-                            enabled: true,
-                            logdir: None,
-                            kill_signal: Some("SIGTERM".into()),
-                            exit_count: 0,
-                        });
+                    if let Ok(Some(exit_status)) = old.child.try_wait() {
+                        self.exit_history.lock().unwrap().record(
+                            app_name,
+                            ExitRecord::new(&old, exit_status, ExitTrigger::Reload),
+                        );
                     }
-                    /* TODO: restart instead of ignore if already started */
-                    let result = self.try_start_app_by_name(app_name).unwrap_or_else(|e| e);
-                    let _ = writeln!(stream, "{result}");
-                } else {
-                    let _ = writeln!(
-                        stream,
-                        "usage: add <name> </path/to/app> <param1> <param2> ..."
-                    );
                 }
+            };
+
+            /* with stop_first the old one is gone when the new one fails, start it again,
+             * otherwise it's still running, the rollout just stops here */
+            let abort = |e: String| -> String {
+                if !stop_first {
+                    return e;
+                }
+                let respawned = {
+                    let mut config_lock = self.config.lock().unwrap();
+                    match config_lock.find_config(app_name) {
+                        Some(app_config) => Self::spawn_app(app_config, instance),
+                        None => return e,
+                    }
+                };
+                match respawned {
+                    Ok(process_child) => {
+                        let mut table_lock = self.processes_table.lock().unwrap();
+                        table_lock.push(process_child);
+                        StateFile::save(&table_lock);
+                        format!("{e}, #{instance} started again")
+                    }
+                    Err(respawn_e) => format!("{e}, #{instance} failed to start again: {respawn_e}"),
+                }
+            };
+
+            let old = if stop_first { take_old() } else { None };
+            stop_old(old);
+
+            let spawned = {
+                let mut config_lock = self.config.lock().unwrap();
+                let app_config = config_lock
+                    .find_config(app_name)
+                    .ok_or(format!("The App name {app_name} can't be found in config"))?;
+                Self::spawn_app(app_config, instance).map(|new| {
+                    let ready_timeout = Duration::from_secs(app_config.ready_timeout.unwrap_or(10));
+                    let readiness = match (app_config.notify, app_config.port) {
+                        (Some(true), _) => Readiness::Notify,
+                        (_, Some(port)) => Readiness::Port(port as u32 + instance),
+                        _ => Readiness::Alive,
+                    };
+                    (new, ready_timeout, readiness)
+                })
+            };
+            let (mut new, ready_timeout, readiness) = spawned.map_err(|e| {
+                abort(format!("[pm][Error] reload of {app_name} aborted, #{instance} failed to spawn: {e}"))
+            })?;
+            /* so its notify messages are not taken as from the old one */
+            self.notify_states
+                .lock()
+                .unwrap()
+                .insert(new.child.id(), NotifyState::default());
+
+            /* config is not locked while waiting */
+            if let Err(e) = self.wait_ready(&mut new.child, readiness, ready_timeout) {
+                let _ = new.child.kill();
+                let _ = new.child.wait();
+                self.notify_states.lock().unwrap().remove(&new.child.id());
+                return Err(abort(format!(
+                    "[pm][Error] reload of {app_name} aborted, #{instance} not ready: {e}"
+                )));
             }
-            "remove" => {
-                // TODO:
+
+            let old = if stop_first { None } else { take_old() };
+            {
+                let mut table_lock = self.processes_table.lock().unwrap();
+                table_lock.push(new);
+                StateFile::save(&table_lock);
             }
-            /* "ls" */
-            cmd if cmd.starts_with("l") => {
-                /* TODO\: show as a beautiful table */
-                // let _ = writeln!(stream, "{:#?}", self.processes_table.lock().unwrap());
-                // let _ = writeln!(stream, "{:#?}", self.config.lock().unwrap());
+            stop_old(old);
+            report(format!("[pm][Info] {app_name} #{instance} replaced and ready"));
+        }
+        self.count_restart(app_name, "reload");
+        Ok(format!("[pm][Info] {app_name} reloaded"))
+    }
 
-                if false {
-                    use prettytable::{Table, row};
-                    let mut table = Table::new();
-                    table.add_row(row!["Config Path"]);
-                    table.add_row(row![self.config.lock().unwrap().config_filepath.display()]);
-                    let _ = writeln!(stream, "{}", table);
+    /**
+     * write a line to stdin of all running instances, needs `stdin = "pipe"`
+     */
+    fn try_send_app_by_name(self: &Self, app_name: &str, text: &str) -> Result<String, String> {
+        let describe = |display_name: &str, written: std::io::Result<()>| match written {
+            Ok(()) => format!("[pm][Info] sent to {display_name}"),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                format!("[pm][Warn] {display_name} is not reading stdin, pipe is full")
+            }
+            Err(e) => format!("[pm][Error] failed to send to {display_name}: {e}"),
+        };
+        let mut table_lock = self.processes_table.lock().unwrap();
+        let multiple = table_lock.iter().filter(|p| p.name == app_name).count() > 1;
+        let mut results = vec![];
+        /* pty writes may block, they are done after the table is unlocked */
+        let mut pty_masters = vec![];
+        for process_child in table_lock
+            .iter_mut()
+            .filter(|process_child| process_child.name == app_name)
+        {
+            let display_name = match multiple {
+                true => format!("{app_name} #{}", process_child.instance),
+                false => app_name.to_string(),
+            };
+            let line = format!("{text}\n");
+            let written = match (&process_child.pty, process_child.child.stdin()) {
+                (Some(pty), _) => match pty.master.try_clone() {
+                    Ok(master) => {
+                        pty_masters.push((display_name, master));
+                        continue;
+                    }
+                    Err(e) => Err(e),
+                },
+                /* the pipe is non-blocking, so a full pipe doesn't hang the daemon */
+                (None, Some(stdin)) => stdin.write_all(line.as_bytes()),
+                (None, None) => {
+                    return Err(format!(
+                        "[pm][Warn] {app_name} has no stdin pipe, set stdin = \"pipe\" in config"
+                    ));
+                }
+            };
+            results.push(describe(&display_name, written));
+        }
+        drop(table_lock);
+        for (display_name, mut master) in pty_masters {
+            /* enter key of a terminal is \r */
+            let written = master.write_all(format!("{text}\r").as_bytes());
+            results.push(describe(&display_name, written));
+        }
+        match results.is_empty() {
+            true => Err(format!("[pm][Warn] {app_name} seems not started")),
+            false => Ok(results.join("\n")),
+        }
+    }
 
-                    let mut table = Table::new();
-                    table.add_row(row![
-                        "", "Name", "Enabled", "Command", "Args", "Cwd", "Log Dir"
-                    ]);
-                    for (i, app) in self.config.lock().unwrap().apps.iter().enumerate() {
-                        table.add_row(row![
-                            i + 1,
-                            app.name,
-                            if app.enabled { "√" } else { "" },
-                            app.cmd,
-                            app.args.join(""),
-                            app.cwd.display(),
-                            app.logdir
-                                .as_ref()
-                                .unwrap_or(&PathBuf::from(DEFAULT_LOG_DIR))
-                                .display()
-                        ]);
+    /**
+     * processes tagged with PM_APP which are not managed by this daemon,
+     * i.e. neither forked by it nor by an app in processes_table,
+     * e.g. left by an earlier daemon which was killed hard
+     */
+    fn find_orphans(self: &Self) -> Vec<Orphan> {
+        let managed: Vec<u32> = self
+            .processes_table
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|process_child| process_child.child.id())
+            .chain(std::iter::once(std::process::id()))
+            .collect();
+        let Ok(entries) = fs::read_dir("/proc") else {
+            return vec![];
+        };
+        let mut orphans = vec![];
+        for pid in entries.filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok()) {
+            /* other users' environ is not readable, they are not ours anyway */
+            let Ok(environ) = fs::read(format!("/proc/{pid}/environ")) else {
+                continue;
+            };
+            let var = |key: &str| {
+                environ.split(|b| *b == 0).find_map(|entry| {
+                    let entry = String::from_utf8_lossy(entry);
+                    entry
+                        .strip_prefix(&format!("{key}="))
+                        .map(str::to_string)
+                })
+            };
+            let Some(app_name) = var("PM_APP") else {
+                continue;
+            };
+            /* walk up parents, pid 1 or 0 means it's not under us */
+            let mut ancestor = pid;
+            let under_managed = loop {
+                if managed.contains(&ancestor) {
+                    break true;
+                }
+                match proc_ppid(ancestor) {
+                    Some(ppid) if ppid > 1 => ancestor = ppid,
+                    _ => break false,
+                }
+            };
+            if under_managed {
+                continue;
+            }
+            orphans.push(Orphan {
+                pid,
+                app_name,
+                instance: var("PM_INSTANCE_ID"),
+                cmdline: proc_cmdline(pid).unwrap_or_default().join(" "),
+            });
+        }
+        orphans
+    }
+
+    /**
+     * freeze (`pause = true`) or thaw all instances, they stay in the table,
+     * watchdog leaves paused ones alone
+     */
+    fn try_pause_app_by_name(self: &Self, app_name: &str, pause: bool) -> Result<String, String> {
+        let mut table_lock = self.processes_table.lock().unwrap();
+        let mut results = vec![];
+        for process_child in table_lock
+            .iter_mut()
+            .filter(|process_child| process_child.name == app_name)
+        {
+            let pid = process_child.child.id();
+            let display_name = format!("{app_name} (pid {pid})");
+            results.push(match (pause, &process_child.paused) {
+                (true, Some(_)) => format!("[pm][Info] {display_name} is already paused"),
+                (false, None) => format!("[pm][Info] {display_name} is not paused"),
+                (true, None) => match Freezer::freeze(pid) {
+                    Ok(freezer) => {
+                        let result =
+                            format!("[pm][Info] {display_name} paused by {}", freezer.describe());
+                        process_child.paused = Some(freezer);
+                        result
                     }
-                    let _ = write!(stream, "{}", table);
+                    Err(e) => format!("[pm][Error] failed to pause {display_name}: {e}"),
+                },
+                (false, Some(freezer)) => match freezer.thaw() {
+                    Ok(()) => {
+                        process_child.paused = None;
+                        /* watchdog deadline starts over */
+                        if let Some(state) = self.notify_states.lock().unwrap().get_mut(&pid) {
+                            state.last_watchdog = Some(time::Instant::now());
+                        }
+                        format!("[pm][Info] {display_name} resumed")
+                    }
+                    Err(e) => format!("[pm][Error] failed to resume {display_name}: {e}"),
+                },
+            });
+        }
+        match results.is_empty() {
+            true => Err(format!("[pm][Warn] {app_name} seems not started")),
+            false => Ok(results.join("\n")),
+        }
+    }
+
+    /**
+     * deliver `signal` to every instance, whatever it does to the app,
+     * if it exits, watchdog handles it as usual
+     */
+    fn try_signal_app_by_name(
+        self: &Self,
+        app_name: &str,
+        signal: nix::sys::signal::Signal,
+    ) -> Result<String, String> {
+        let pids = self.instance_pids(app_name);
+        if pids.is_empty() {
+            return Err(format!("[pm][Warn] {app_name} seems not started"));
+        }
+        let results = pids
+            .iter()
+            .map(|pid| {
+                match nix::sys::signal::kill(nix::unistd::Pid::from_raw(*pid as i32), signal) {
+                    Ok(()) => format!("[pm][Info] sent {signal} to {app_name} (pid {pid})"),
+                    Err(e) => format!("[pm][Error] failed to send {signal} to pid {pid}: {e}"),
+                }
+            })
+            .collect::<Vec<_>>();
+        Ok(results.join("\n"))
+    }
+
+    /**
+     * see `AppConfig::reload_signal`
+     */
+    fn try_reload_config_app_by_name(self: &Self, app_name: &str) -> Result<String, String> {
+        let (reload_cmd, reload_signal, hooks) = {
+            let config_lock = self.config.lock().unwrap();
+            let Some(app_config) = config_lock.apps.iter().find(|app| app.name == app_name) else {
+                return Err(format!("[pm][Warn] {app_name} not found"));
+            };
+            (
+                app_config.reload_cmd.clone(),
+                app_config.reload_signal.clone(),
+                app_config.hooks.clone(),
+            )
+        };
+        let Some(reload_cmd) = reload_cmd else {
+            let signal = parse_signal(reload_signal.as_deref().unwrap_or("SIGHUP"))
+                .map_err(|e| format!("[pm][Error] {e}"))?;
+            return self.try_signal_app_by_name(app_name, signal);
+        };
+
+        let pids = self.instance_pids(app_name);
+        if pids.is_empty() {
+            return Err(format!("[pm][Warn] {app_name} seems not started"));
+        }
+        /* once per instance, config is not locked while the command runs */
+        let mut results = vec![];
+        for pid in pids {
+            results.push(
+                match hooks.run_cmd(app_name, "reload", &reload_cmd, &[("MAINPID", pid.to_string())])
+                {
+                    Ok(()) => format!("[pm][Info] {app_name} (pid {pid}) reloaded by reload_cmd"),
+                    Err(e) => format!("[pm][Error] {app_name} (pid {pid}): {e}"),
+                },
+            );
+        }
+        Ok(results.join("\n"))
+    }
+
+    /**
+     * see `AppConfig::ready_timeout`
+     */
+    fn wait_ready(
+        self: &Self,
+        child: &mut AppProcess,
+        readiness: Readiness,
+        timeout: Duration,
+    ) -> Result<(), String> {
+        const MIN_UPTIME: Duration = Duration::from_secs(1);
+        let started = time::Instant::now();
+        loop {
+            if let Ok(Some(exit_status)) = child.try_wait() {
+                return Err(format!("exited with {}", describe_exit_status(exit_status)));
+            }
+            let ready = match readiness {
+                Readiness::Notify => self
+                    .notify_states
+                    .lock()
+                    .unwrap()
+                    .get(&child.id())
+                    .is_some_and(|state| state.ready),
+                Readiness::Port(port) => u16::try_from(port).is_ok_and(|port| {
+                    std::net::TcpStream::connect_timeout(
+                        &std::net::SocketAddr::from(([127, 0, 0, 1], port)),
+                        Duration::from_millis(200),
+                    )
+                    .is_ok()
+                }),
+                Readiness::Alive => started.elapsed() >= MIN_UPTIME,
+            };
+            if ready {
+                return Ok(());
+            }
+            if started.elapsed() > timeout {
+                return Err(format!("timeout after {}s", timeout.as_secs()));
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn try_stop_app_by_name(
+        self: &Self,
+        app_name: &str,
+        trigger: ExitTrigger,
+    ) -> Result<String, &'static str> {
+        self.try_stop_instances(app_name, 0, trigger)
+    }
+
+    /**
+     * stop instances whose id >= `from_instance`, so 0 means all of them,
+     * `trigger` is what their exits are recorded as
+     */
+    fn try_stop_instances(
+        self: &Self,
+        app_name: &str,
+        from_instance: u32,
+        trigger: ExitTrigger,
+    ) -> Result<String, &'static str> {
+        let (signal, hooks) = {
+            let mut config_lock = self.config.lock().unwrap();
+            let app_config = config_lock.find_config(&app_name).unwrap();
+            (app_config.kill_signal(), app_config.hooks.clone())
+        };
+
+        /* take them out of table first, so table is not locked while waiting them to die */
+        let mut to_stop: Vec<ProcessChild> = {
+            let mut table_lock = self.processes_table.lock().unwrap();
+            let (to_stop, rest) = table_lock.drain(..).partition(|process_child| {
+                process_child.name == app_name && process_child.instance >= from_instance
+            });
+            *table_lock = rest;
+            if !to_stop.is_empty() {
+                StateFile::save(&table_lock);
+            }
+            to_stop
+        };
+
+        if to_stop.is_empty() {
+            return Err("[pm][Warn] Seems not started, do nothing");
+        }
+        /* highest id first */
+        to_stop.sort_by_key(|process_child| std::cmp::Reverse(process_child.instance));
+
+        /* only those about to stop, e.g. the removed ones on `scale` down */
+        let mut hook_warnings = vec![];
+        let pids = to_stop
+            .iter()
+            .map(|process_child| process_child.child.id().to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if let Err(e) = hooks.run(app_name, "pre_stop", &[("PM_APP_PID", pids)]) {
+            hook_warnings.push(format!("[pm][Warn] {app_name}: {e}"));
+        }
+        for process_child in to_stop.iter_mut() {
+            process_child.resume_if_paused();
+            /* kill must borrow as mutable */
+            let _ = Self::nice_kill_process(
+                &mut process_child.child,
+                signal,
+                time::Duration::from_millis(2000),
+            );
+            self.notify_states
+                .lock()
+                .unwrap()
+                .remove(&process_child.child.id());
+        }
+
+        let (pids, exit_statuses): (Vec<String>, Vec<String>) = to_stop
+            .iter_mut()
+            .map(|process_child| {
+                let exit_status = match process_child.child.try_wait() {
+                    Ok(Some(exit_status)) => {
+                        self.exit_history.lock().unwrap().record(
+                            app_name,
+                            ExitRecord::new(process_child, exit_status, trigger),
+                        );
+                        describe_exit_status(exit_status)
+                    }
+                    _ => "unknown".to_string(),
+                };
+                (process_child.child.id().to_string(), exit_status)
+            })
+            .unzip();
+        let envs = [
+            ("PM_APP_PID", pids.join(" ")),
+            ("PM_EXIT_STATUS", exit_statuses.join(", ")),
+        ];
+        if let Err(e) = hooks.run(app_name, "post_stop", &envs) {
+            hook_warnings.push(format!("[pm][Warn] {app_name}: {e}"));
+        }
+
+        let result = match to_stop.as_slice() {
+            [process_child] if process_child.instance == 0 => {
+                format!("[pm][Info] {app_name} was killed successfully")
+            }
+            _ => format!(
+                "[pm][Info] {app_name} #{} was killed successfully",
+                to_stop
+                    .iter()
+                    .map(|process_child| process_child.instance.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", #")
+            ),
+        };
+        Ok(std::iter::once(result)
+            .chain(hook_warnings)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /**
+     * change scheduling settings of a running app, pm.toml is not touched,
+     * so it falls back to configured values on next spawn.
+     * with empty settings, just print current effective values
+     */
+    fn try_sched_app_by_name(
+        self: &Self,
+        app_name: &str,
+        sched: &SchedConfig,
+    ) -> Result<String, String> {
+        let mut instances: Vec<(u32, u32)> = self
+            .processes_table
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|process_child| process_child.name == app_name)
+            .map(|process_child| (process_child.instance, process_child.child.id()))
+            .collect();
+        if instances.is_empty() {
+            return Err(format!("[pm][Warn] {app_name} seems not started"));
+        }
+        instances.sort();
+        let params = sched
+            .resolve()
+            .map_err(|e| format!("[pm][Error] failed to apply to {app_name}: {e}"))?;
+
+        let mut results = vec![];
+        for (instance, pid) in instances {
+            if !sched.is_empty() {
+                /* also apply to threads, nice and affinity are per-thread on linux */
+                let tids: Vec<u32> = fs::read_dir(format!("/proc/{pid}/task"))
+                    .map(|entries| {
+                        entries
+                            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+                            .collect()
+                    })
+                    .unwrap_or_else(|_| vec![pid]);
+                for tid in tids {
+                    params
+                        .apply(tid)
+                        .map_err(|e| format!("[pm][Error] failed to apply to {app_name}: {e}"))?;
+                }
+            }
+            results.push(format!(
+                "[pm][Info] {app_name} #{instance}: {}",
+                read_sched_settings(pid)
+            ));
+        }
+        Ok(results.join("\n"))
+    }
+
+    /**
+     * spawn app according to its config, scheduling settings are applied before exec,
+     * if they can't be (e.g. negative nice without permission), spawn fails with that error
+     */
+    fn spawn_app(app_config: &AppConfig, instance: u32) -> std::io::Result<ProcessChild> {
+        let mut envs = vec![];
+        for path in &app_config.env_file {
+            envs.extend(read_env_file(&app_config.cwd.join(path))?);
+        }
+        envs.extend(app_config.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        /* PM_APP tags the app and everything it forks, see `doctor orphans` */
+        envs.push(("PM_APP".to_string(), app_config.name.clone()));
+        envs.push(("PM_INSTANCE_ID".to_string(), instance.to_string()));
+        if let Some(port) = app_config.port {
+            envs.push(("PORT".to_string(), (port as u32 + instance).to_string()));
+        }
+
+        let (program, args) = if app_config.listen_fds.is_empty() {
+            (app_config.cmd.clone(), app_config.args.clone())
+        } else {
+            envs.push(("LISTEN_FDS".into(), app_config.listen_fds.len().to_string()));
+            envs.push((
+                "LISTEN_FDNAMES".into(),
+                app_config
+                    .listen
+                    .iter()
+                    .map(|listen| listen.name.clone().unwrap_or(app_config.name.clone()))
+                    .collect::<Vec<_>>()
+                    .join(":"),
+            ));
+            /* LISTEN_PID must be pid of the app itself, only known after fork, let sh do it */
+            let args = [
+                "-c".to_string(),
+                "LISTEN_PID=$$; export LISTEN_PID; exec \"$0\" \"$@\"".to_string(),
+                app_config.cmd.clone(),
+            ]
+            .into_iter()
+            .chain(app_config.args.iter().cloned())
+            .collect();
+            ("/bin/sh".to_string(), args)
+        };
+        let pass_fds: Vec<RawFd> = app_config.listen_fds.iter().map(|fd| fd.as_raw_fd()).collect();
+        if app_config.notify_socket.is_some() {
+            envs.push((
+                "NOTIFY_SOCKET".into(),
+                app_config.notify_socket_path().display().to_string(),
+            ));
+            if let Some(watchdog_sec) = app_config.watchdog_sec {
+                envs.push(("WATCHDOG_USEC".into(), (watchdog_sec * 1_000_000).to_string()));
+            }
+        }
+
+        let log = File::create(app_config.log_file(instance))?;
+        let (stdio, pty) = if app_config.tty.unwrap_or(false) {
+            let pty = nix::pty::openpty(None, None).map_err(io::Error::from)?;
+            /* not to leak into other children */
+            set_cloexec(pty.master.as_raw_fd(), true)?;
+            envs.push(("TERM".into(), env::var("TERM").unwrap_or("xterm".into())));
+            let stdio = [
+                Stdio::from(pty.slave.try_clone()?),
+                Stdio::from(pty.slave.try_clone()?),
+                Stdio::from(pty.slave),
+            ];
+            (stdio, Some(File::from(pty.master)))
+        } else {
+            let stdio = [
+                app_config.stdin()?,
+                Stdio::from(log.try_clone()?),
+                Stdio::from(log.try_clone()?),
+            ];
+            (stdio, None)
+        };
+
+        let child = Self::spawn_process(
+            &program,
+            &args,
+            envs,
+            &app_config.cwd,
+            &pass_fds,
+            stdio,
+            pty.is_some(),
+            app_config.sched.resolve()?,
+        )?;
+        if let Some(stdin) = &child.stdin {
+            let fd = stdin.as_raw_fd();
+            unsafe {
+                let flags = libc::fcntl(fd, libc::F_GETFL);
+                libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+            }
+        }
+
+        let pty = pty.map(|master| {
+            let pty = Arc::new(Pty {
+                master,
+                attached: Mutex::new(None),
+                scrollback: Mutex::new(vec![]),
+            });
+            pty.start_reader(log);
+            pty
+        });
+        Ok(ProcessChild {
+            name: app_config.name.clone(),
+            instance,
+            child: AppProcess::Spawned(child),
+            started_at: time::SystemTime::now(),
+            pty,
+            paused: None,
+            killed_for: None,
+        })
+    }
+
+    /**
+     * low level process operations, don't depend on context
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_process<S, I, E, K, V>(
+        program: S,
+        args: I,
+        envs: E,
+        cwd: &Path,
+        pass_fds: &[RawFd],
+        stdio: [Stdio; 3],
+        controlling_tty: bool,
+        sched: SchedParams,
+    ) -> std::io::Result<std::process::Child>
+    where
+        S: AsRef<OsStr>,
+        I: IntoIterator<Item = S>,
+        E: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        let mut command = Command::new(program);
+        /* own process group, so `pause` can stop the app with all its children, not daemon */
+        if !controlling_tty {
+            command.process_group(0);
+        }
+        if controlling_tty {
+            /* new session, with stdin (the pty slave) as its controlling terminal */
+            unsafe {
+                command.pre_exec(|| {
+                    if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        if !pass_fds.is_empty() {
+            let pass_fds = pass_fds.to_vec();
+            /* only async-signal-safe calls here, it runs between fork and exec */
+            unsafe {
+                command.pre_exec(move || {
+                    /* move them above the target range first, so they don't overwrite each other */
+                    let min_fd = 3 + pass_fds.len() as libc::c_int;
+                    let mut tmp_fds = [0 as RawFd; 64];
+                    for (i, fd) in pass_fds.iter().take(tmp_fds.len()).enumerate() {
+                        tmp_fds[i] = libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, min_fd);
+                        if tmp_fds[i] < 0 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    }
+                    /* dup2 clears CLOEXEC of the target */
+                    for (i, tmp_fd) in tmp_fds.iter().take(pass_fds.len()).enumerate() {
+                        if libc::dup2(*tmp_fd, 3 + i as libc::c_int) < 0 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    }
+                    Ok(())
+                });
+            }
+        }
+        unsafe {
+            command.pre_exec(move || sched.apply(0));
+        }
+        let [stdin, stdout, stderr] = stdio;
+        command
+            .args(args)
+            .envs(envs)
+            .current_dir(cwd)
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn()
+    }
+
+    fn nice_kill_process(
+        process: &mut AppProcess,
+        signal: nix::sys::signal::Signal,
+        nice_wait: time::Duration,
+    ) -> Result<(), std::io::Error> {
+        fn kill_process(process: &mut AppProcess) -> Result<(), std::io::Error> {
+            if let Ok(()) = process.kill() {
+                process.wait()?;
+            } else {
+                println!("Process {} has already exited", process.id());
+            }
+            Ok(())
+        }
+
+        let pid = nix::unistd::Pid::from_raw(process.id() as i32);
+        match nix::sys::signal::kill(pid, signal) {
+            // match nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL) {
+            Ok(()) => {
+                let expire = time::Instant::now() + nice_wait;
+                while let Ok(None) = process.try_wait() {
+                    if time::Instant::now() > expire {
+                        break;
+                    }
+                    std::thread::sleep(nice_wait / 10);
+                }
+                if let Ok(None) = process.try_wait() {
+                    kill_process(process)?;
                 }
+            }
+            Err(nix::Error::EINVAL) => {
+                println!("Invalid signal. Killing process {}", pid);
+                kill_process(process)?;
+            }
+            Err(nix::Error::EPERM) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Insufficient permissions to signal process {}", pid),
+                ));
+            }
+            Err(nix::Error::ESRCH) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Process {} does not exist", pid),
+                ));
+            }
+            Err(e) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Unexpected error {}", e),
+                ));
+            }
+        };
+        Ok(())
+    }
+}
 
-                {
-                    use tabled::{builder::Builder, settings::Style};
 
-                    let mut b = Builder::new();
-                    b.push_column([
-                        "Config Path",
-                        self.config
-                            .lock()
-                            .unwrap()
-                            .config_filepath
-                            .display()
-                            .to_string()
-                            .as_str(),
-                    ]);
-                    let mut table = b.build();
-                    table.with(Style::modern());
-                    let _ = writeln!(stream, "{}", table);
+/**
+ * how `reload` decides a new instance is ready, see `AppConfig::ready_timeout`
+ */
+#[derive(Debug, Clone, Copy)]
+enum Readiness {
+    /* READY=1 received */
+    Notify,
+    /* 127.0.0.1:<port> accepts connections */
+    Port(u32),
+    /* still alive after a while */
+    Alive,
+}
 
-                    let mut b = Builder::new();
-                    b.push_record([
-                        "",
-                        "Name",
-                        "Enabled",
-                        "Status",
-                        "Exit Count",
-                        "Cmd",
-                        "Args",
-                        "Cwd",
-                        "Log Dir",
-                        "Kill Signal",
-                    ]);
-                    for (i, app) in self.config.lock().unwrap().apps.iter().enumerate() {
-                        b.push_record([
-                            (i + 1).to_string().as_str(),
-                            &app.name,
-                            if app.enabled { "√" } else { "" },
-                            self.processes_table
-                                .lock()
-                                .unwrap()
-                                .iter_mut()
-                                .find(|x| x.name == app.name)
-                                .map_or_else(
-                                    || "Not Started",
-                                    |process_child| match process_child.child.try_wait() {
-                                        Ok(Some(_)) => "Exited",
-                                        Ok(None) => "Running",
-                                        Err(_) => "Error",
-                                    },
-                                ),
-                            &app.exit_count.to_string(),
-                            &app.cmd,
-                            &app.args.join(""),
-                            &app.cwd.display().to_string(),
-                            &app.logdir
-                                .as_ref()
-                                .unwrap_or(&PathBuf::from(DEFAULT_LOG_DIR))
-                                .display()
-                                .to_string(),
-                            &app.kill_signal
-                                .as_ref()
-                                .unwrap_or(&"SIGTERM".to_string())
-                                .to_string(),
-                        ]);
-                    }
+/**
+ * one datagram from NOTIFY_SOCKET, with sender's pid from SCM_CREDENTIALS
+ */
+fn recv_with_pid(fd: RawFd) -> std::io::Result<(String, Option<u32>)> {
+    let mut buf = [0u8; 4096];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    /* u64 for alignment of cmsghdr */
+    let mut cmsg_buf = [0u64; 16];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = std::mem::size_of_val(&cmsg_buf) as _;
 
-                    let mut table = b.build();
-                    table.with(Style::modern());
-                    let _ = writeln!(stream, "{}", table);
-                }
+    let n = unsafe { libc::recvmsg(fd, &mut msg, libc::MSG_DONTWAIT) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut sender_pid = None;
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_CREDENTIALS
+            {
+                let cred = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::ucred);
+                sender_pid = Some(cred.pid as u32);
             }
-            /* "restart" */
-            cmd if cmd.starts_with("r") => {
-                /* TODO: apply to first app if no name provided */
-                if self.config.lock().unwrap().apps.len() >= 1 {
-                    let app_name = &if params.len() >= 1 {
-                        params[0].to_string()
-                    } else {
-                        self.config.lock().unwrap().apps[0].name.clone()
-                    };
-                    let _ = writeln!(stream, "[pm][Info] restarting {app_name}... ");
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    Ok((String::from_utf8_lossy(&buf[..n as usize]).into_owned(), sender_pid))
+}
 
-                    let result = self
-                        .try_stop_app_by_name(app_name)
-                        .unwrap_or_else(|e| e.to_string());
-                    let _ = writeln!(stream, "{result}");
+/**
+ * `SchedConfig` checked and turned into syscall arguments,
+ * so it can be applied between fork and exec, where nothing may allocate
+ */
+#[derive(Debug, Clone, Copy, Default)]
+struct SchedParams {
+    nice: Option<libc::c_int>,
+    ioprio: Option<libc::c_int>,
+    cpuset: Option<nix::sched::CpuSet>,
+    oom_score_adj: Option<i32>,
+}
 
-                    let result = self.try_start_app_by_name(app_name).unwrap_or_else(|e| e);
-                    let _ = writeln!(stream, "{result}");
-                } else {
-                    // let _ = writeln!(stream, "usage: restart <name>");
-                    let _ = writeln!(
-                        stream,
-                        "[pm][Warn] No Apps available, please add with `add` first"
-                    );
+impl SchedConfig {
+    fn resolve(&self) -> std::io::Result<SchedParams> {
+        let mut params = SchedParams {
+            nice: self.nice,
+            oom_score_adj: self.oom_score_adj,
+            ..Default::default()
+        };
+
+        if self.ionice_class.is_some() || self.ionice_level.is_some() {
+            /* see linux/ioprio.h */
+            const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+            let class = match self.ionice_class.as_deref().unwrap_or("best-effort") {
+                "realtime" | "rt" | "1" => 1,
+                "best-effort" | "be" | "2" => 2,
+                "idle" | "3" => 3,
+                other => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("unknown ionice class {other:?}"),
+                    ));
                 }
+            };
+            /* idle class has no level */
+            let level = if class == 3 {
+                0
+            } else {
+                self.ionice_level.unwrap_or(4) as libc::c_int
+            };
+            if level > 7 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("ionice level should be 0~7, got {level}"),
+                ));
             }
-            /* "enable" */
-            cmd if cmd.starts_with("e") => {
-                if self.config.lock().unwrap().apps.len() >= 1 {
-                    let app_name = &if params.len() >= 1 {
-                        params[0].to_string()
-                    } else {
-                        self.config.lock().unwrap().apps[0].name.clone()
-                    };
+            params.ioprio = Some((class << IOPRIO_CLASS_SHIFT) | level);
+        }
 
-                    let _ = writeln!(stream, "Enable {app_name}");
-                    self.config.lock().unwrap().enable(app_name, true);
-                    let result = self.try_start_app_by_name(app_name).unwrap_or_else(|e| e);
-                    let _ = writeln!(stream, "{result}");
-                } else {
-                    // let _ = writeln!(stream, "usage: disable <name>");
-                    let _ = writeln!(
-                        stream,
-                        "[pm][Warn] No Apps available, please add with `add` first"
-                    );
-                }
+        if let Some(cpu_list) = &self.cpu_affinity {
+            let mut cpuset = nix::sched::CpuSet::new();
+            for cpu in parse_cpu_list(cpu_list)? {
+                cpuset.set(cpu).map_err(io::Error::from)?;
             }
-            /* "disable" */
-            cmd if cmd.starts_with("d") => {
-                if self.config.lock().unwrap().apps.len() >= 1 {
-                    let app_name = &if params.len() >= 1 {
-                        params[0].to_string()
-                    } else {
-                        self.config.lock().unwrap().apps[0].name.clone()
-                    };
+            params.cpuset = Some(cpuset);
+        }
+        Ok(params)
+    }
+}
 
-                    let _ = writeln!(stream, "[pm][Info] disabling and stopping {app_name} ...");
-                    // thread::sleep(Duration::from_millis(2000));
-                    self.config.lock().unwrap().enable(app_name, false);
+impl SchedParams {
+    /**
+     * nice and affinity only affect the given thread id,
+     * for a fresh child it's the same as the process,
+     * 0 is the calling process, then it only makes syscalls, safe for `pre_exec`
+     */
+    fn apply(&self, pid: u32) -> std::io::Result<()> {
+        if let Some(nice) = self.nice {
+            /* setpriority returns -1 on error */
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, nice) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
 
-                    let result = self
-                        .try_stop_app_by_name(app_name)
-                        .unwrap_or_else(|e| e.to_string());
-                    let _ = writeln!(stream, "{result}");
+        if let Some(ioprio) = self.ioprio {
+            const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+            if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, pid, ioprio) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
 
-                    /* this syntax also works, but... weird? */
-                    // let _ = try_stop_process(app_name, processes_table).map_err(|e| {
-                    //     let _ = writeln!(stream, "{e}");
-                    // });
-                } else {
-                    // let _ = writeln!(stream, "usage: disable <name>");
-                    let _ = writeln!(
-                        stream,
-                        "[pm][Warn] No Apps available, please add with `add` first"
-                    );
+        if let Some(cpuset) = &self.cpuset {
+            nix::sched::sched_setaffinity(nix::unistd::Pid::from_raw(pid as i32), cpuset)
+                .map_err(io::Error::from)?;
+        }
+
+        if let Some(oom_score_adj) = self.oom_score_adj {
+            if pid != 0 {
+                return fs::write(format!("/proc/{pid}/oom_score_adj"), oom_score_adj.to_string());
+            }
+            /* no format! here, digits are put in a stack buffer */
+            let mut buf = [0u8; 12];
+            let mut i = buf.len();
+            let mut n = oom_score_adj.unsigned_abs();
+            loop {
+                i -= 1;
+                buf[i] = b'0' + (n % 10) as u8;
+                n /= 10;
+                if n == 0 {
+                    break;
                 }
             }
-            /* spawn all */
-            "on" => {}
-            "quit" => {
-                let _ = writeln!(stream, "[pm][Info] stopping all apps...");
-                self.stop_all_apps();
-                let _ = writeln!(stream, "[pm][Info] all apps stopped");
-                std::process::exit(0);
+            if oom_score_adj < 0 {
+                i -= 1;
+                buf[i] = b'-';
             }
-            cmd => {
-                let _ = writeln!(stream, "[pm][Warn] Unknown command: {cmd}");
+            unsafe {
+                let fd = libc::open(c"/proc/self/oom_score_adj".as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let written = libc::write(fd, buf[i..].as_ptr().cast(), buf.len() - i);
+                libc::close(fd);
+                if written < 0 {
+                    return Err(io::Error::last_os_error());
+                }
             }
         }
+        Ok(())
     }
+}
 
-    fn start_watchdog_loop(self: &Self) {
-        let cloned_config = self.config.clone();
-        let cloned_processes_table = self.processes_table.clone();
-        let handler = Self {
-            config: cloned_config,
-            processes_table: cloned_processes_table,
-        };
-        thread::spawn(move || {
-            loop {
-                thread::sleep(Duration::from_secs(3));
-                let mut processes_table_lock = handler.processes_table.lock().unwrap();
-                for process_child in processes_table_lock.iter_mut() {
-                    if let Ok(Some(_)) = process_child.child.try_wait() {
-                        /* exited */
-                        println!(
-                            "[pm][Info] {} exited! try to restart...",
-                            process_child.name
-                        );
-                        if let Some(app_config) = handler
-                            .config
-                            .lock()
-                            .unwrap()
-                            .find_config(&process_child.name)
-                        {
-                            app_config.exit_count += 1;
-                            if let Ok(child) = Self::spawn_process(
-                                &app_config.cmd,
-                                &app_config.args,
-                                app_config
-                                    .logdir
-                                    .clone()
-                                    .unwrap_or(DEFAULT_LOG_DIR.into())
-                                    .join(app_config.name.clone() + ".log"),
-                            ) {
-                                process_child.child = child;
-                            } else {
-                                eprintln!("[pm][Error] failed to restart {}", process_child.name);
-                            }
-                        } else {
-                            eprintln!(
-                                "[pm][Error] why {} is in the process table but not in app configs?",
-                                process_child.name
-                            );
-                        }
-                    }
-                }
-            }
-        });
-    }
+/**
+ * (state, start time) from /proc/<pid>/stat, fields 3 and 22,
+ * comm may contain spaces so split after ')'
+ */
+fn proc_stat(pid: u32) -> Option<(char, u64)> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let (_, rest) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    Some((fields.first()?.chars().next()?, fields.get(19)?.parse().ok()?))
+}
 
+/**
+ * cgroup v2 dir of a process ("self" for the daemon), from /proc/<pid>/cgroup
+ */
+fn proc_cgroup_dir(pid: &str) -> Option<PathBuf> {
+    let content = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    let cgroup = content.lines().find_map(|line| line.strip_prefix("0::"))?;
+    Some(Path::new("/sys/fs/cgroup").join(cgroup.trim_start_matches('/')))
+}
 
-    fn start_all_apps(self: &Self) {
-        let config = self.config.lock().unwrap();
-        for app_config in &config.apps {
-            let res = self.try_start_app(&app_config);
-            println!("{}", res.as_ref().unwrap_or_else(|e| e));
-            match res {
-                Ok(o) => {
-                    println!("{o}");
-                }
-                Err(e) => println!("[pm][Error] {e}"),
-            }
-        }
+/**
+ * kills by the OOM killer in a cgroup, "oom_kill" in its memory.events
+ */
+fn cgroup_oom_kills(dir: &Path) -> Option<u64> {
+    fs::read_to_string(dir.join("memory.events"))
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill ")?.trim().parse().ok())
+}
+
+/**
+ * utime + stime from /proc/<pid>/stat, fields 14 and 15, in clock ticks
+ */
+fn proc_cpu_ticks(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let (_, rest) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    Some(fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?)
+}
+
+/**
+ * VmRSS from /proc/<pid>/status
+ */
+fn proc_rss_kb(pid: u32) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+/**
+ * where `cmd` would be run from, relative to `cwd` if it has a '/', otherwise found in PATH
+ */
+fn resolve_executable(cmd: &str, cwd: &Path) -> Option<PathBuf> {
+    if cmd.contains('/') {
+        return fs::canonicalize(cwd.join(cmd)).ok();
     }
-    fn stop_all_apps(self: &Self) {
-        let app_names: Vec<String> = {
-            let processes_table = self.processes_table.lock().unwrap();
-            let app_names = processes_table.iter().map(|p| p.name.clone()).collect();
-            app_names
-        };
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(cmd))
+        .find(|path| path.is_file())
+}
 
-        for app_name in app_names {
-            let _ = self.try_stop_app_by_name(&app_name);
+/**
+ * e.g. "1.5 MiB"
+ */
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    for unit in ["KiB", "MiB", "GiB"] {
+        if size < 1024.0 {
+            return format!("{size:.1} {unit}");
         }
+        size /= 1024.0;
     }
+    format!("{size:.1} TiB")
+}
 
+/**
+ * e.g. "2d 3h 4m 5s", leading zero units left out
+ */
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let parts = [(secs / 86400, "d"), (secs / 3600 % 24, "h"), (secs / 60 % 60, "m")];
+    let mut result: String = parts
+        .iter()
+        .skip_while(|(n, _)| *n == 0)
+        .map(|(n, unit)| format!("{n}{unit} "))
+        .collect();
+    result.push_str(&format!("{}s", secs % 60));
+    result
+}
 
-    fn try_start_app_by_name(self: &Self, app_name: &str) -> Result<String, String> {
-        let mut config_lock = self.config.lock().unwrap();
-        let app_config = config_lock.find_config(&app_name);
-        if let Some(app_config) = app_config {
-            self.try_start_app(app_config)
-            // let _ = try_start_process(app_config, processes_table.clone());
-        } else {
-            Err(format!("The App name {app_name} can't be found in config"))
-        }
+/**
+ * a process found by `doctor orphans`
+ */
+struct Orphan {
+    pid: u32,
+    app_name: String,
+    instance: Option<String>,
+    cmdline: String,
+}
+
+impl std::fmt::Display for Orphan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[pm][Warn] orphan pid {} of {} #{}: {}",
+            self.pid,
+            self.app_name,
+            self.instance.as_deref().unwrap_or("?"),
+            self.cmdline
+        )
     }
+}
 
-    /**
-     * this do some checks for you:
-     * 1. the app is enabled
-     * 2. the app is not started (i.e. not in the table)
-     */
-    fn try_start_app(self: &Self, app_config: &AppConfig) -> Result<String, String> {
-        let app_name = &app_config.name;
-        // let mut config_lock = config.lock().unwrap();
-        // let app_config = config_lock.find_config(&app_name);
+/**
+ * SIGTERM, then SIGKILL to those still alive after 2s
+ */
+fn kill_orphans(pids: &[u32]) -> String {
+    use nix::sys::signal::{Signal, kill};
+    let alive = |pid: &u32| matches!(proc_stat(*pid), Some((state, _)) if state != 'Z');
+    for pid in pids {
+        let _ = kill(nix::unistd::Pid::from_raw(*pid as i32), Signal::SIGTERM);
+    }
+    let expire = time::Instant::now() + Duration::from_secs(2);
+    while pids.iter().any(alive) && time::Instant::now() < expire {
+        thread::sleep(Duration::from_millis(100));
+    }
+    let stubborn: Vec<&u32> = pids.iter().filter(|pid| alive(pid)).collect();
+    for pid in &stubborn {
+        let _ = kill(nix::unistd::Pid::from_raw(**pid as i32), Signal::SIGKILL);
+    }
+    format!(
+        "[pm][Info] killed {} orphans, {} of them with SIGKILL",
+        pids.len(),
+        stubborn.len()
+    )
+}
 
+/**
+ * field 4 of /proc/<pid>/stat
+ */
+fn proc_ppid(pid: u32) -> Option<u32> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
 
-        // if let Some(app_config) = app_config {
-        if app_config.enabled {
-            let index_in_table = self
-                .processes_table
-                .lock()
-                .unwrap()
-                .iter()
-                .position(|process_child| &process_child.name == app_name);
+fn set_cloexec(fd: RawFd, cloexec: bool) -> std::io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    let flags = match cloexec {
+        true => flags | libc::FD_CLOEXEC,
+        false => flags & !libc::FD_CLOEXEC,
+    };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFD, flags) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
 
-            if let None = index_in_table {
-                // let _ = writeln!(stream, "Let's spawn");
-                if let Ok(child) = Self::spawn_process(
-                    &app_config.cmd,
-                    &app_config.args,
-                    app_config
-                        .logdir
-                        .clone()
-                        .unwrap_or(DEFAULT_LOG_DIR.into())
-                        .join(app_config.name.clone() + ".log"),
-                ) {
-                    self.processes_table.lock().unwrap().push(ProcessChild {
-                        name: app_name.to_string(),
-                        child: child,
-                    });
-                    Ok(format!("[pm][Info] {app_name} was spawned successfully"))
-                } else {
-                    Err(format!("[pm][Error] {app_name} was failed to spawn"))
+fn proc_cmdline(pid: u32) -> Option<Vec<String>> {
+    let cmdline = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    Some(
+        cmdline
+            .split(|b| *b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect(),
+    )
+}
+
+/**
+ * read back effective values from /proc, for `ls` and `sched`
+ * e.g. "nice=-5 io=rt:0 cpus=2-3 oom=-1000"
+ */
+fn read_sched_settings(pid: u32) -> String {
+    /* field 19 of /proc/<pid>/stat, comm may contain spaces so split after ')' */
+    let nice = fs::read_to_string(format!("/proc/{pid}/stat"))
+        .ok()
+        .and_then(|stat| {
+            let (_, rest) = stat.rsplit_once(')')?;
+            rest.split_whitespace().nth(16).map(String::from)
+        })
+        .unwrap_or("?".into());
+
+    let ioprio = unsafe { libc::syscall(libc::SYS_ioprio_get, 1, pid) };
+    let io = match (ioprio >> 13, ioprio & 0xff) {
+        _ if ioprio < 0 => "?".to_string(),
+        /* none means derived from nice, shown as best-effort */
+        (0, _) => format!("be:{}", (nice.parse::<i64>().unwrap_or(0) + 20) / 5),
+        (1, level) => format!("rt:{level}"),
+        (2, level) => format!("be:{level}"),
+        (3, _) => "idle".to_string(),
+        (class, level) => format!("{class}:{level}"),
+    };
+
+    let cpus = fs::read_to_string(format!("/proc/{pid}/status"))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+                .map(|list| list.trim().to_string())
+        })
+        .unwrap_or("?".into());
+
+    let oom = fs::read_to_string(format!("/proc/{pid}/oom_score_adj"))
+        .map(|s| s.trim().to_string())
+        .unwrap_or("?".into());
+
+    format!("nice={nice} io={io} cpus={cpus} oom={oom}")
+}
+
+/**
+ * "0-2,5" => [0, 1, 2, 5]
+ */
+fn parse_cpu_list(cpu_list: &str) -> std::io::Result<Vec<usize>> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid cpu list {cpu_list:?}"),
+        )
+    };
+    let mut cpus = vec![];
+    for part in cpu_list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let start: usize = start.trim().parse().map_err(|_| invalid())?;
+                let end: usize = end.trim().parse().map_err(|_| invalid())?;
+                if start > end {
+                    return Err(invalid());
                 }
-            } else {
-                Err(format!("[pm][Info] {app_name} has already been started"))
+                cpus.extend(start..=end);
             }
-        } else {
-            Err(format!("[pm][Info] {app_name} is disabled"))
+            None => cpus.push(part.parse().map_err(|_| invalid())?),
         }
-        // } else {
-        //     Err("The App name can't be found in config")
-        // }
     }
+    if cpus.is_empty() {
+        return Err(invalid());
+    }
+    Ok(cpus)
+}
 
-    fn try_stop_app_by_name(self: &Self, app_name: &str) -> Result<String, &'static str> {
-        let signal = nix::sys::signal::Signal::from_str(
-            &self
-                .config
-                .lock()
-                .unwrap()
-                .find_config(&app_name)
-                .unwrap()
-                .kill_signal
-                .clone()
-                .unwrap_or("SIGTERM".into()),
-        )
-        .unwrap_or(nix::sys::signal::Signal::SIGTERM);
 
-        let index_in_table = self
-            .processes_table
-            .lock()
-            .unwrap()
-            .iter()
-            .position(|process_child| process_child.name == app_name);
+/**
+ * minimal cron expression: "minute hour day-of-month month day-of-week",
+ * each field supports `*`, `n`, `a-b`, lists by `,` and steps by `/`
+ * (e.g. `0-30/10`, or `*` with `/15` for every 15 minutes),
+ * day-of-week 0~7 (0 and 7 are sunday), and shortcuts `@hourly`, `@daily`...
+ * like vixie cron, if both day fields are restricted, either matches
+ */
+#[derive(Debug)]
+struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
 
-        if let Some(index) = index_in_table {
-            {
-                /* ISSUE: what's this? why this keep locked? */
-                let mut table_lock = self.processes_table.lock().unwrap();
-                /* kill must borrow as mutable */
-                let _ = Self::nice_kill_process(
-                    &mut table_lock[index].child,
-                    signal,
-                    time::Duration::from_millis(2000),
-                );
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let expr = match expr.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expr => expr,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(format!("expect 5 fields, got {:?}", expr));
+        };
+
+        fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+            let invalid = || format!("invalid cron field {field:?}, range {min}~{max}");
+            let mut bits = 0u64;
+            for part in field.split(',') {
+                let (range, step) = match part.split_once('/') {
+                    Some((range, step)) => (range, step.parse().map_err(|_| invalid())?),
+                    None => (part, 1),
+                };
+                let (start, end) = match range {
+                    "*" => (min, max),
+                    _ => match range.split_once('-') {
+                        Some((a, b)) => (
+                            a.parse().map_err(|_| invalid())?,
+                            b.parse().map_err(|_| invalid())?,
+                        ),
+                        /* "5/10" means from 5 to max */
+                        None if part.contains('/') => (range.parse().map_err(|_| invalid())?, max),
+                        None => {
+                            let n = range.parse().map_err(|_| invalid())?;
+                            (n, n)
+                        }
+                    },
+                };
+                if step == 0 || start < min || end > max || start > end {
+                    return Err(invalid());
+                }
+                for n in (start..=end).step_by(step) {
+                    bits |= 1 << n;
+                }
             }
-            self.processes_table.lock().unwrap().remove(index);
-            Ok(format!("[pm][Info] {app_name} was killed successfully"))
-        } else {
-            Err("[pm][Warn] Seems not started, do nothing")
+            Ok(bits)
+        }
+
+        let mut days_of_week = parse_field(dow, 0, 7)?;
+        /* 7 is also sunday */
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
         }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(dom, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week,
+            dom_restricted: !dom.starts_with('*'),
+            dow_restricted: !dow.starts_with('*'),
+        })
     }
+}
 
+impl CronSchedule {
     /**
-     * low level process operations, don't depend on context
+     * first matching minute strictly after `after`, None if nothing in 5 years
+     * (e.g. "0 0 31 2 *")
      */
-    fn spawn_process<S: AsRef<OsStr>, P: AsRef<Path>, I: IntoIterator<Item = S>>(
-        program: S,
-        args: I,
-        log_file: P,
-    ) -> std::io::Result<std::process::Child> {
-        let log = File::create(log_file)?;
-        /* TODO: implement cwd */
-        Command::new(program)
-            .args(args)
-            .stdout(Stdio::from(log.try_clone()?))
-            .stderr(Stdio::from(log))
-            .spawn()
-    }
-
-    fn nice_kill_process(
-        process: &mut std::process::Child,
-        signal: nix::sys::signal::Signal,
-        nice_wait: time::Duration,
-    ) -> Result<(), std::io::Error> {
-        fn kill_process(process: &mut std::process::Child) -> Result<(), std::io::Error> {
-            if let Ok(()) = process.kill() {
-                process.wait()?;
+    fn next_after(&self, after: time::SystemTime) -> Option<time::SystemTime> {
+        let after = after.duration_since(time::UNIX_EPOCH).ok()?.as_secs() as i64;
+        let mut t = after / 60 * 60 + 60;
+        let limit = t + 5 * 366 * 24 * 3600;
+        while t < limit {
+            let tm = local_tm(t);
+            let day_matches = {
+                let dom = self.days_of_month & (1 << tm.tm_mday) != 0;
+                let dow = self.days_of_week & (1 << tm.tm_wday) != 0;
+                match (self.dom_restricted, self.dow_restricted) {
+                    (true, true) => dom || dow,
+                    (true, false) => dom,
+                    (false, true) => dow,
+                    (false, false) => true,
+                }
+            };
+            if self.months & (1 << (tm.tm_mon + 1)) == 0 || !day_matches {
+                /* jump to next midnight */
+                t += ((24 - tm.tm_hour as i64) * 60 - tm.tm_min as i64) * 60;
+            } else if self.hours & (1 << tm.tm_hour) == 0 {
+                t += (60 - tm.tm_min as i64) * 60;
+            } else if self.minutes & (1 << tm.tm_min) == 0 {
+                t += 60;
             } else {
-                println!("Process {} has already exited", process.id());
+                return Some(time::UNIX_EPOCH + Duration::from_secs(t as u64));
             }
-            Ok(())
         }
+        None
+    }
+}
 
-        let pid = nix::unistd::Pid::from_raw(process.id() as i32);
-        match nix::sys::signal::kill(pid, signal) {
-            // match nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL) {
-            Ok(()) => {
-                let expire = time::Instant::now() + nice_wait;
-                while let Ok(None) = process.try_wait() {
-                    if time::Instant::now() > expire {
-                        break;
-                    }
-                    std::thread::sleep(nice_wait / 10);
-                }
-                if let Ok(None) = process.try_wait() {
-                    kill_process(process)?;
-                }
-            }
-            Err(nix::Error::EINVAL) => {
-                println!("Invalid signal. Killing process {}", pid);
-                kill_process(process)?;
-            }
-            Err(nix::Error::EPERM) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    format!("Insufficient permissions to signal process {}", pid),
-                ));
-            }
-            Err(nix::Error::ESRCH) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    format!("Process {} does not exist", pid),
-                ));
+fn local_tm(secs: i64) -> libc::tm {
+    let t = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&t, &mut tm) };
+    tm
+}
+
+/**
+ * e.g. "2025-06-01 03:30:00", in local time
+ */
+fn format_local_time(t: time::SystemTime) -> String {
+    let secs = match t.duration_since(time::UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(_) => return "?".into(),
+    };
+    let tm = local_tm(secs);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+/**
+ * `add` params into a new app, returns (app, no_start, force),
+ * flags go before <cmd>, everything after <cmd> or `--` is taken as is
+ */
+fn parse_add_params(params: &[String]) -> Result<(AppConfig, bool, bool), String> {
+    let mut app_config = AppConfig {
+        cwd: PathBuf::from("/"),
+        enabled: true,
+        kill_signal: Some("SIGTERM".into()),
+        ..Default::default()
+    };
+    let (mut no_start, mut force) = (false, false);
+    let mut positionals: Vec<String> = vec![];
+    let mut iter = params.iter();
+    while let Some(param) = iter.next() {
+        if positionals.len() >= 2 || !param.starts_with("--") {
+            positionals.push(param.clone());
+            continue;
+        }
+        if param == "--" {
+            positionals.extend(iter.by_ref().cloned());
+            break;
+        }
+        let mut value = || iter.next().cloned().ok_or(format!("`{param}` needs a value"));
+        match param.as_str() {
+            "--cwd" => app_config.cwd = value()?.into(),
+            "--env" => {
+                let env = value()?;
+                let Some((key, val)) = env.split_once('=') else {
+                    return Err(format!("--env expects KEY=VALUE, got {env:?}"));
+                };
+                app_config.env.insert(key.to_string(), val.to_string());
             }
-            Err(e) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    format!("Unexpected error {}", e),
-                ));
+            "--env-file" => app_config.env_file.push(value()?.into()),
+            "--logdir" => app_config.logdir = Some(value()?.into()),
+            "--kill-signal" => {
+                /* stored as "SIGINT" even if given as "int" or "2" */
+                app_config.kill_signal = Some(parse_signal(&value()?)?.as_str().to_string());
             }
+            "--restart" => app_config.restart = Some(value()?.parse()?),
+            "--no-start" => no_start = true,
+            "--force" => force = true,
+            _ => return Err(format!("unknown flag `{param}`")),
+        }
+    }
+    let mut positionals = positionals.into_iter();
+    let (Some(name), Some(cmd)) = (positionals.next(), positionals.next()) else {
+        return Err("usage: add <name> [flags] [--] <cmd> [args]...".into());
+    };
+    if name.matches('/').count() > 1 {
+        return Err(format!("name should be <name> or <namespace>/<name>, got {name:?}"));
+    }
+    if name.contains('.') {
+        return Err(format!("name and namespace can't contain '.', got {name:?}"));
+    }
+    app_config.namespace = name.split_once('/').map(|(ns, _)| ns.to_string());
+    app_config.name = name;
+    app_config.cmd = cmd;
+    app_config.args = positionals.collect();
+    Ok((app_config, no_start, force))
+}
+
+/**
+ * KEY=VALUE lines, blank lines and # comments skipped,
+ * an `export ` prefix and quotes around the value are fine
+ */
+fn read_env_file(path: &Path) -> std::io::Result<Vec<(String, String)>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("env_file {}: {e}", path.display())))?;
+    let mut envs = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("env_file {}:{}: expect KEY=VALUE", path.display(), i + 1),
+            ));
         };
-        Ok(())
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+            .unwrap_or(value);
+        envs.push((key.trim().to_string(), value.to_string()));
+    }
+    Ok(envs)
+}
+
+/**
+ * "SIGHUP", "HUP", "hup" or signal number "1"
+ */
+fn parse_signal(s: &str) -> Result<nix::sys::signal::Signal, String> {
+    use nix::sys::signal::Signal;
+    if let Ok(number) = s.parse::<i32>() {
+        return Signal::try_from(number).map_err(|_| format!("invalid signal number {number}"));
     }
+    let name = s.to_uppercase();
+    let name = match name.starts_with("SIG") {
+        true => name,
+        false => format!("SIG{name}"),
+    };
+    Signal::from_str(&name).map_err(|_| format!("invalid signal {s:?}"))
 }
 
+/**
+ * e.g. "exit 1", "signal 9 (SIGKILL)", "signal 11 (SIGSEGV, core dumped)"
+ */
+fn describe_exit_status(exit_status: ExitStatus) -> String {
+    if let Some(code) = exit_status.code() {
+        return format!("exit {code}");
+    }
+    match exit_status.signal() {
+        Some(sig) => {
+            let name = nix::sys::signal::Signal::try_from(sig)
+                .map(|s| s.as_str())
+                .unwrap_or("?");
+            if exit_status.core_dumped() {
+                format!("signal {sig} ({name}, core dumped)")
+            } else {
+                format!("signal {sig} ({name})")
+            }
+        }
+        None => exit_status.to_string(),
+    }
+}
 
 fn main() -> std::io::Result<()> {
     register_sigint()?;
 
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: {} <command>", &args[0]);
+    if args.len() < 2 || matches!(args[1].as_str(), "help" | "--help" | "-h") {
+        match args.get(2).and_then(|name| CommandSpec::find(name)) {
+            Some(spec) => println!("{}", spec.help()),
+            None => println!("{}", CommandSpec::overview()),
+        }
         return Ok(());
     }
-    let command = &args[1];
+    let Some(spec) = CommandSpec::find(&args[1]) else {
+        eprintln!("[pm][Error] unknown command `{}`, see `pm help`", args[1]);
+        std::process::exit(2);
+    };
+    let command = spec.name;
     let params = &args[2..];
 
-    if command == "daemon" {
-        daemonize_self()?;
+    /* exec by `daemon upgrade`, not for humans */
+    let upgraded = command == "daemon" && params.first().is_some_and(|p| p == "--upgrade");
+    if params
+        .iter()
+        .take_while(|p| *p != "--")
+        .any(|p| p == "--help" || p == "-h")
+        && !upgraded
+    {
+        println!("{}", spec.help());
+        return Ok(());
+    }
+    if let (false, Err(e)) = (upgraded, spec.validate(params)) {
+        eprintln!("[pm][Error] {e}\n{}", spec.usage());
+        std::process::exit(2);
+    }
+
+    if command == "daemon" && params.first().is_some_and(|p| p == "upgrade") {
+        main_cli("upgrade", &[])
+    } else if command == "daemon" {
+        /* exec by `daemon upgrade`, already daemonized, keep the pid */
+        let (listener_fd, params) = match params {
+            [flag, fd, rest @ ..] if flag == "--upgrade" => {
+                relock_pid_file().unwrap_or_else(|e| {
+                    eprintln!("[pm][Warn] failed to lock {PID_PATH} again: {e}")
+                });
+                (fd.parse::<RawFd>().ok(), rest)
+            }
+            _ => {
+                daemonize_self()?;
+                (None, params)
+            }
+        };
         main_daemon(
             params
-                .get(0)
+                .first()
                 .map(|s| Path::new(s))
                 .unwrap_or(env::home_dir().unwrap().join("pm.toml").as_path()),
+            listener_fd,
         )
+    } else if command == "attach" {
+        attach_cli(params)
+    } else if command == "add" {
+        main_cli(command, &absolute_add_params(params)?)
+    } else if command == "edit" {
+        edit_cli(params)
     } else {
         main_cli(command, params)
     }
 }
 
+/**
+ * a command goes to daemon as bincode of [command, params...]
+ */
+fn send_command(stream: &mut UnixStream, command: &str, params: &[String]) -> std::io::Result<()> {
+    bincode::encode_into_std_write(
+        std::iter::once(command.to_string())
+            .chain(params.iter().cloned())
+            .collect::<Vec<String>>(),
+        stream,
+        bincode::config::standard(),
+    )
+    .map(|_| ())
+    .map_err(std::io::Error::other)
+}
+
+/**
+ * send a command and wait for the whole response, None if daemon is not there
+ */
+fn daemon_request(command: &str, params: &[String]) -> std::io::Result<Option<String>> {
+    let Ok(mut stream) = UnixStream::connect(SOCKET_PATH) else {
+        return Ok(None);
+    };
+    send_command(&mut stream, command, params)?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(Some(response))
+}
+
+/**
+ * `edit`: the app as toml in $VISUAL or $EDITOR (vi by default),
+ * sent back to daemon on save, reopened with daemon's errors on top until it's taken
+ */
+fn edit_cli(params: &[String]) -> std::io::Result<()> {
+    let Some(snippet) = daemon_request("edit", &params[..1])? else {
+        /* let main_cli print the usual hint */
+        return main_cli("edit", params);
+    };
+    if snippet.starts_with("[pm][") {
+        print!("{snippet}");
+        return Ok(());
+    }
+    let editor = env::var("VISUAL")
+        .or(env::var("EDITOR"))
+        .unwrap_or("vi".to_string());
+    let path = env::temp_dir().join(format!("pm-edit-{}.toml", std::process::id()));
+    let mut content = format!("# save and quit to apply, an empty file to cancel\n{snippet}");
+    loop {
+        fs::write(&path, &content)?;
+        /* $EDITOR may come with args, e.g. "code --wait" */
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{editor} \"$@\""))
+            .arg("sh")
+            .arg(&path)
+            .status()?;
+        if !status.success() {
+            println!("[pm][Warn] {editor} {status}, nothing changed");
+            break;
+        }
+        /* errors of the last round were put on top as comments */
+        let edited: String = fs::read_to_string(&path)?
+            .lines()
+            .filter(|line| !line.starts_with("# [pm]"))
+            .map(|line| format!("{line}\n"))
+            .collect();
+        if edited.lines().all(|line| line.trim().is_empty() || line.trim().starts_with('#')) {
+            println!("[pm][Info] nothing changed");
+            break;
+        }
+        let Some(response) = daemon_request("edit", &[params[0].clone(), edited.clone()])? else {
+            println!("[pm][Error] daemon is gone, your edit is left in {}", path.display());
+            return Ok(());
+        };
+        print!("{response}");
+        if !response.contains("[pm][Error]") {
+            break;
+        }
+        content = response
+            .lines()
+            .map(|line| match line.starts_with("[pm]") {
+                true => format!("# {line}\n"),
+                false => format!("# [pm] {line}\n"),
+            })
+            .chain(std::iter::once(edited))
+            .collect();
+    }
+    let _ = fs::remove_file(&path);
+    Ok(())
+}
+
+/**
+ * daemon has its own cwd, so make paths of `add` absolute here, and --cwd defaults to ours
+ */
+fn absolute_add_params(params: &[String]) -> std::io::Result<Vec<String>> {
+    let cwd = env::current_dir()?;
+    let mut result = vec![];
+    let mut has_cwd = false;
+    let mut positionals = 0;
+    let mut iter = params.iter();
+    while let Some(param) = iter.next() {
+        result.push(param.clone());
+        if positionals >= 2 || param == "--" {
+            result.extend(iter.cloned());
+            break;
+        }
+        match param.as_str() {
+            "--cwd" | "--logdir" | "--env-file" => {
+                has_cwd |= param == "--cwd";
+                if let Some(path) = iter.next() {
+                    result.push(cwd.join(path).display().to_string());
+                }
+            }
+            "--env" | "--kill-signal" | "--restart" => result.extend(iter.next().cloned()),
+            param if !param.starts_with("--") => positionals += 1,
+            _ => {}
+        }
+    }
+    if !has_cwd {
+        result.splice(0..0, ["--cwd".to_string(), cwd.display().to_string()]);
+    }
+    Ok(result)
+}
+
+/**
+ * client side grammar of a command, params are checked against it
+ * before anything is sent to daemon, daemon gets the canonical name
+ */
+struct CommandSpec {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    /* positionals, "<required>", "literal", "[optional]", and "..." at the end takes the rest */
+    args: &'static str,
+    /* ("--flag", help) or ("--flag <value>", help) */
+    flags: &'static [(&'static str, &'static str)],
+    about: &'static str,
+}
+
+const APPS_HELP: &str = "<apps>: a name, `all`, a glob like `worker-*`, `tag:<tag>`, `ns:<namespace>` or index in `ls`";
+
+const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "daemon",
+        aliases: &[],
+        args: "[/path/to/pm.toml|upgrade]",
+        flags: &[],
+        about: "start daemon (config defaults to ~/pm.toml), or re-exec a running one with `upgrade`",
+    },
+    CommandSpec {
+        name: "status",
+        aliases: &[],
+        args: "",
+        flags: &[],
+        about: "check if daemon is running",
+    },
+    CommandSpec {
+        name: "ls",
+        aliases: &["list"],
+        args: "[<apps>]",
+        flags: &[("--group-by <tag|namespace>", "one table per tag or namespace")],
+        about: "list apps and their status",
+    },
+    CommandSpec {
+        name: "add",
+        aliases: &[],
+        args: "<name> </path/to/app> [args]...",
+        flags: &[
+            ("--cwd <dir>", "working directory, default the current one"),
+            ("--env <KEY=VALUE>", "set an environment variable, repeatable"),
+            ("--env-file <path>", "read KEY=VALUE lines at every start, repeatable"),
+            ("--logdir <dir>", "where logs go"),
+            ("--kill-signal <signal>", "signal to stop the app, default SIGTERM"),
+            ("--restart <always|on-failure|never>", "when to restart on exit, default always"),
+            ("--no-start", "only add it to config"),
+            ("--force", "replace an app with the same name"),
+        ],
+        about: "add an app to config and start it, flags go before the app, or use `--`",
+    },
+    CommandSpec {
+        name: "show",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "all settings of an app, with pid, uptime, last exit, cpu, memory and logs",
+    },
+    CommandSpec {
+        name: "history",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "recent exits of an app, with exit code or signal, uptime and why",
+    },
+    CommandSpec {
+        name: "edit",
+        aliases: &[],
+        args: "<app>",
+        flags: &[],
+        about: "edit the config of an app in $EDITOR, restarted if running",
+    },
+    CommandSpec {
+        name: "remove",
+        aliases: &["rm"],
+        args: "<apps>",
+        flags: &[("--delete-logs", "also delete its log files, kept by default")],
+        about: "stop an app and remove it from config",
+    },
+    CommandSpec {
+        name: "start",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "start an app now, config is not changed",
+    },
+    CommandSpec {
+        name: "stop",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "stop an app until `start`, config is not changed",
+    },
+    CommandSpec {
+        name: "enable",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "start the app with daemon (saved in config), it's not started now",
+    },
+    CommandSpec {
+        name: "disable",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "don't start the app with daemon (saved in config), it's not stopped now",
+    },
+    CommandSpec {
+        name: "restart",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "stop and start an app",
+    },
+    CommandSpec {
+        name: "reload",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "replace instances one at a time, each waits for the new one to be ready",
+    },
+    CommandSpec {
+        name: "reload-config",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "ask an app to re-read its config, by reload_cmd or reload_signal",
+    },
+    CommandSpec {
+        name: "run",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "run a oneshot or cron app now",
+    },
+    CommandSpec {
+        name: "scale",
+        aliases: &[],
+        args: "<apps> <instances>",
+        flags: &[],
+        about: "change number of instances",
+    },
+    CommandSpec {
+        name: "sched",
+        aliases: &[],
+        args: "<apps> [nice=N|ionice=CLASS[:LEVEL]|cpus=LIST|oom=N]...",
+        flags: &[],
+        about: "change scheduling settings of a running app, or show them",
+    },
+    CommandSpec {
+        name: "signal",
+        aliases: &[],
+        args: "<apps> <SIGNAME|number>",
+        flags: &[],
+        about: "send a signal to an app, it's still managed as usual",
+    },
+    CommandSpec {
+        name: "pause",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "freeze an app, by cgroup freezer or SIGSTOP",
+    },
+    CommandSpec {
+        name: "resume",
+        aliases: &[],
+        args: "<apps>",
+        flags: &[],
+        about: "resume a paused app",
+    },
+    CommandSpec {
+        name: "send",
+        aliases: &[],
+        args: "<apps> <text>...",
+        flags: &[],
+        about: "write a line to stdin of an app (stdin = \"pipe\" or tty)",
+    },
+    CommandSpec {
+        name: "attach",
+        aliases: &[],
+        args: "<app> [instance]",
+        flags: &[],
+        about: "connect your terminal to a tty app, detach with Ctrl-]",
+    },
+    CommandSpec {
+        name: "watch",
+        aliases: &[],
+        args: "<apps> [on|off]",
+        flags: &[],
+        about: "show or toggle restart on file changes",
+    },
+    CommandSpec {
+        name: "doctor",
+        aliases: &[],
+        args: "orphans",
+        flags: &[("--kill", "kill the orphans found")],
+        about: "find processes of apps not managed by daemon",
+    },
+    CommandSpec {
+        name: "quit",
+        aliases: &[],
+        args: "",
+        flags: &[],
+        about: "stop all apps and daemon",
+    },
+];
+
+impl CommandSpec {
+    fn find(name: &str) -> Option<&'static CommandSpec> {
+        COMMANDS
+            .iter()
+            .find(|spec| spec.name == name || spec.aliases.contains(&name))
+    }
+
+    fn overview() -> String {
+        let mut lines = vec![
+            "usage: pm <command> [args], see `pm <command> --help` for details".to_string(),
+            APPS_HELP.to_string(),
+            String::new(),
+            "commands:".to_string(),
+        ];
+        for spec in COMMANDS {
+            let name = match spec.aliases {
+                [] => spec.name.to_string(),
+                aliases => format!("{} ({})", spec.name, aliases.join(", ")),
+            };
+            lines.push(format!("  {name:<18} {}", spec.about));
+        }
+        lines.join("\n")
+    }
+
+    fn usage(&self) -> String {
+        let flags = self
+            .flags
+            .iter()
+            .map(|(flag, _)| format!(" [{flag}]"))
+            .collect::<String>();
+        format!("usage: pm {} {}{flags}", self.name, self.args)
+    }
+
+    fn help(&self) -> String {
+        let mut lines = vec![self.usage(), String::new(), self.about.to_string()];
+        if self.args.contains("<app") {
+            lines.push(APPS_HELP.to_string());
+        }
+        if !self.aliases.is_empty() {
+            lines.push(format!("aliases: {}", self.aliases.join(", ")));
+        }
+        if !self.flags.is_empty() {
+            lines.push(String::new());
+            lines.push("flags:".to_string());
+            for (flag, help) in self.flags {
+                lines.push(format!("  {flag:<18} {help}"));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /**
+     * check positionals count and flags, once the variadic positional is reached,
+     * the rest are taken as is (so `send app --verbose` works)
+     */
+    fn validate(&self, params: &[String]) -> Result<(), String> {
+        let positionals: Vec<&str> = self.args.split_whitespace().collect();
+        let required = positionals.iter().filter(|arg| !arg.starts_with('[')).count();
+        let variadic = positionals.last().is_some_and(|arg| arg.ends_with("..."));
+        let mut count = 0;
+        let mut iter = params.iter();
+        while let Some(param) = iter.next() {
+            if variadic && count + 1 >= positionals.len() {
+                count += 1 + iter.len();
+                break;
+            }
+            /* the rest are positionals even if they look like flags */
+            if param == "--" {
+                count += iter.len();
+                break;
+            }
+            if param.starts_with("--") {
+                let Some((flag, _)) = self
+                    .flags
+                    .iter()
+                    .find(|(flag, _)| flag.split_whitespace().next() == Some(param))
+                else {
+                    return Err(format!("unknown flag `{param}`"));
+                };
+                if flag.contains(' ') && iter.next().is_none() {
+                    return Err(format!("`{param}` needs a value"));
+                }
+                continue;
+            }
+            count += 1;
+        }
+        if count < required {
+            return Err(format!("missing {}", positionals[count].trim_end_matches("...")));
+        }
+        if !variadic && count > positionals.len() {
+            return Err("too many arguments".to_string());
+        }
+        Ok(())
+    }
+}
 
 fn main_cli(command: &str, params: &[String]) -> std::io::Result<()> {
     match UnixStream::connect(SOCKET_PATH) {
         Ok(mut stream) => {
-            bincode::encode_into_std_write(
-                &std::iter::once(command.to_string())
-                    .chain(params.iter().cloned())
-                    .collect::<Vec<String>>(),
-                &mut stream,
-                bincode::config::standard(),
-            )
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            send_command(&mut stream, command, params)?;
 
 
             /* read altogether, wait everything written */
@@ -767,15 +5659,139 @@ fn main_cli(command: &str, params: &[String]) -> std::io::Result<()> {
     Ok(())
 }
 
+/**
+ * like `main_cli`, but after daemon accepts, the terminal is put in raw mode,
+ * keys are sent to the app and its output is printed, until Ctrl-] or the app exits
+ */
+fn attach_cli(params: &[String]) -> std::io::Result<()> {
+    use nix::sys::termios;
+
+    let mut stream = match UnixStream::connect(SOCKET_PATH) {
+        Ok(stream) => stream,
+        /* let main_cli print the usual hint */
+        Err(_) => return main_cli("attach", params),
+    };
+    send_command(&mut stream, "attach", params)?;
+
+    /* byte by byte, what follows the first line is raw output of the app */
+    let mut first_line = vec![];
+    let mut byte = [0u8; 1];
+    while stream.read(&mut byte)? == 1 && byte[0] != b'\n' {
+        first_line.push(byte[0]);
+    }
+    let first_line = String::from_utf8_lossy(&first_line).trim_end().to_string();
+    println!("{first_line}");
+    if !first_line.starts_with("[pm][Info] attached") {
+        return Ok(());
+    }
+
+    let stdin = io::stdin();
+    let saved = termios::tcgetattr(&stdin).map_err(io::Error::from)?;
+    let mut raw = saved.clone();
+    termios::cfmakeraw(&mut raw);
+    termios::tcsetattr(&stdin, termios::SetArg::TCSANOW, &raw).map_err(io::Error::from)?;
+    let restore = move |message: &str| {
+        let _ = termios::tcsetattr(io::stdin(), termios::SetArg::TCSANOW, &saved);
+        println!("\r\n{message}");
+    };
+
+    let writer = Arc::new(Mutex::new(stream.try_clone()?));
+    let send_frame = |writer: &Mutex<UnixStream>, kind: u8, payload: &[u8]| {
+        let mut frame = vec![kind];
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(payload);
+        writer.lock().unwrap().write_all(&frame)
+    };
+    let send_winsize = move |writer: &Mutex<UnixStream>| {
+        let mut winsize: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDIN_FILENO, libc::TIOCGWINSZ, &mut winsize) } == 0 {
+            let mut payload = winsize.ws_row.to_be_bytes().to_vec();
+            payload.extend_from_slice(&winsize.ws_col.to_be_bytes());
+            let _ = send_frame(writer, ATTACH_FRAME_WINSIZE, &payload);
+        }
+    };
+    send_winsize(&writer);
+
+    /* forward window size changes */
+    let mut signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGWINCH])?;
+    let winch_writer = writer.clone();
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            send_winsize(&winch_writer);
+        }
+    });
+
+    /* output of the app, daemon closes the connection when app exits */
+    let output_restore = restore.clone();
+    thread::spawn(move || {
+        let mut stdout = io::stdout();
+        let mut buf = [0u8; 4096];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    let _ = stdout.write_all(&buf[..n]);
+                    let _ = stdout.flush();
+                }
+            }
+        }
+        output_restore("[pm][Info] connection closed, the app may have exited");
+        std::process::exit(0);
+    });
+
+    let mut buf = [0u8; 1024];
+    loop {
+        let n = io::stdin().read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let input = &buf[..n];
+        match input.iter().position(|b| *b == DETACH_KEY) {
+            Some(i) => {
+                let _ = send_frame(&writer, ATTACH_FRAME_DATA, &input[..i]);
+                break;
+            }
+            None => {
+                if send_frame(&writer, ATTACH_FRAME_DATA, input).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    let _ = writer.lock().unwrap().shutdown(std::net::Shutdown::Both);
+    restore("[pm][Info] detached");
+    Ok(())
+}
+
 /**
  *
  */
-fn main_daemon(config_filepath: &path::Path) -> std::io::Result<()> {
-    let pmd = ProcessManagerDaemon::new(config_filepath);
-    pmd.start_listening()?;
+fn main_daemon(config_filepath: &path::Path, listener_fd: Option<RawFd>) -> std::io::Result<()> {
+    let pmd = ProcessManagerDaemon::new(config_filepath, listener_fd.is_some());
+    pmd.start_listening(listener_fd)?;
 
     Ok(())
 }
+/**
+ * after `daemon upgrade`, the lock daemonize took on the pid file was CLOEXEC and gone with exec,
+ * take it again so another `pm daemon` still sees this one running
+ */
+fn relock_pid_file() -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(PID_PATH)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    file.set_len(0)?;
+    writeln!(file, "{}", std::process::id())?;
+    /* held as long as this daemon lives, CLOEXEC so the next upgrade takes it again */
+    let _ = file.into_raw_fd();
+    Ok(())
+}
+
 fn daemonize_self() -> std::io::Result<()> {
     println!("[pm][Info] try to start daemon");
     /* TODO: better daemon log dir */
@@ -783,7 +5799,7 @@ fn daemonize_self() -> std::io::Result<()> {
     // let stderr = fs::File::create(env::home_dir().unwrap().join("pm-daemon.err")).unwrap();
 
     let daemonize = daemonize::Daemonize::new()
-        .pid_file("/tmp/pm-daemon.pid") // Every method except `new` and `start`
+        .pid_file(PID_PATH) // Every method except `new` and `start`
         // .chown_pid_file(true) // is optional, see `Daemonize` documentation
         .working_directory(".") // for default behaviour.
         // .user("nobody")
@@ -861,3 +5877,300 @@ fn register_sigint() -> std::io::Result<()> {
     });
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* local time, like cron sees it */
+    fn local(year: i32, month: i32, day: i32, hour: i32, minute: i32) -> time::SystemTime {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        tm.tm_year = year - 1900;
+        tm.tm_mon = month - 1;
+        tm.tm_mday = day;
+        tm.tm_hour = hour;
+        tm.tm_min = minute;
+        tm.tm_isdst = -1;
+        time::UNIX_EPOCH + Duration::from_secs(unsafe { libc::mktime(&mut tm) } as u64)
+    }
+
+    fn next(expr: &str, after: time::SystemTime) -> Option<time::SystemTime> {
+        CronSchedule::from_str(expr).unwrap().next_after(after)
+    }
+
+    fn strings(params: &[&str]) -> Vec<String> {
+        params.iter().map(|param| param.to_string()).collect()
+    }
+
+    #[test]
+    fn cron_steps_and_ranges() {
+        let bits = |ns: &[u32]| ns.iter().fold(0u64, |bits, n| bits | 1 << n);
+        let schedule = CronSchedule::from_str("*/15 0-20/10 5/10 1,6-7 *").unwrap();
+        assert_eq!(schedule.minutes, bits(&[0, 15, 30, 45]));
+        assert_eq!(schedule.hours, bits(&[0, 10, 20]));
+        assert_eq!(schedule.days_of_month, bits(&[5, 15, 25]));
+        assert_eq!(schedule.months, bits(&[1, 6, 7]));
+        assert!(!schedule.dow_restricted);
+
+        assert_eq!(
+            next("*/15 * * * *", local(2025, 6, 4, 10, 7)),
+            Some(local(2025, 6, 4, 10, 15))
+        );
+        assert_eq!(
+            next("0 9-17/4 * * *", local(2025, 6, 4, 13, 0)),
+            Some(local(2025, 6, 4, 17, 0))
+        );
+    }
+
+    #[test]
+    fn cron_invalid() {
+        for expr in [
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+        ] {
+            assert!(CronSchedule::from_str(expr).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn cron_sunday_is_0_and_7() {
+        /* 2025-06-04 is a wednesday */
+        let wednesday = local(2025, 6, 4, 10, 0);
+        assert_eq!(next("0 0 * * 0", wednesday), Some(local(2025, 6, 8, 0, 0)));
+        assert_eq!(next("0 0 * * 7", wednesday), Some(local(2025, 6, 8, 0, 0)));
+        assert_eq!(next("0 0 * * 6-7", wednesday), Some(local(2025, 6, 7, 0, 0)));
+        assert_eq!(next("@weekly", wednesday), Some(local(2025, 6, 8, 0, 0)));
+    }
+
+    #[test]
+    fn cron_day_of_month_or_day_of_week() {
+        /* both restricted, either matches: friday 07-11 comes before the 13th */
+        assert_eq!(
+            next("0 12 13 * 5", local(2025, 7, 8, 0, 0)),
+            Some(local(2025, 7, 11, 12, 0))
+        );
+        assert_eq!(
+            next("0 12 13 * 5", local(2025, 7, 11, 12, 0)),
+            Some(local(2025, 7, 13, 12, 0))
+        );
+        /* only day-of-month restricted */
+        assert_eq!(next("0 0 31 * *", local(2025, 6, 1, 0, 0)), Some(local(2025, 7, 31, 0, 0)));
+        /* `*` day-of-month doesn't widen a restricted day-of-week */
+        assert_eq!(next("0 0 * * 1", local(2025, 6, 4, 0, 0)), Some(local(2025, 6, 9, 0, 0)));
+    }
+
+    #[test]
+    fn cron_next_is_strictly_after() {
+        assert_eq!(
+            next("@daily", local(2025, 6, 4, 0, 0)),
+            Some(local(2025, 6, 5, 0, 0))
+        );
+        assert_eq!(next("0 0 31 2 *", local(2025, 6, 4, 0, 0)), None);
+    }
+
+    #[test]
+    fn paused_child_exits() {
+        let child = Command::new("sleep")
+            .arg("100")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        let mut process_child = ProcessChild {
+            name: "paused".into(),
+            instance: 0,
+            child: AppProcess::Spawned(child),
+            started_at: time::SystemTime::now(),
+            pty: None,
+            paused: None,
+            killed_for: None,
+        };
+        process_child.paused = Some(Freezer::freeze(pid).unwrap());
+        assert!(process_child.try_exit().is_none());
+        assert!(process_child.paused.is_some());
+
+        nix::sys::signal::kill(
+            nix::unistd::Pid::from_raw(pid as i32),
+            nix::sys::signal::Signal::SIGKILL,
+        )
+        .unwrap();
+        let exit_status = (0..50)
+            .find_map(|_| {
+                thread::sleep(Duration::from_millis(20));
+                process_child.try_exit()
+            })
+            .expect("a paused child killed should be seen exited");
+        assert_eq!(exit_status.signal(), Some(libc::SIGKILL));
+        assert!(process_child.paused.is_none());
+    }
+
+    #[test]
+    fn signals() {
+        use nix::sys::signal::Signal;
+        for s in ["SIGHUP", "HUP", "hup", "SigHup", "1"] {
+            assert_eq!(parse_signal(s), Ok(Signal::SIGHUP), "{s}");
+        }
+        assert_eq!(parse_signal("usr2"), Ok(Signal::SIGUSR2));
+        for s in ["", "0", "99", "-1", "SIGFOO", "SIG"] {
+            assert!(parse_signal(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn env_file_quoting() {
+        let path = env::temp_dir().join(format!("pm-test-{}.env", std::process::id()));
+        fs::write(
+            &path,
+            "# comment\n\
+             \n\
+             PLAIN=value\n\
+             export EXPORTED=1\n\
+             DOUBLE=\"a b\"\n\
+             SINGLE='$HOME'\n\
+             UNMATCHED=\"open\n\
+             INNER=a\"b\"c\n\
+             EQUALS=k=v\n\
+             \x20 SPACED = padded \n\
+             EMPTY=\n\
+             EMPTY_QUOTED=\"\"\n",
+        )
+        .unwrap();
+        let envs = read_env_file(&path);
+        let _ = fs::remove_file(&path);
+        let pairs: Vec<(&str, &str)> = [
+            ("PLAIN", "value"),
+            ("EXPORTED", "1"),
+            ("DOUBLE", "a b"),
+            ("SINGLE", "$HOME"),
+            ("UNMATCHED", "\"open"),
+            ("INNER", "a\"b\"c"),
+            ("EQUALS", "k=v"),
+            ("SPACED", "padded"),
+            ("EMPTY", ""),
+            ("EMPTY_QUOTED", ""),
+        ]
+        .into();
+        let envs = envs.unwrap();
+        assert_eq!(
+            envs.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect::<Vec<_>>(),
+            pairs
+        );
+
+        fs::write(&path, "OK=1\n\nbroken line\n").unwrap();
+        let e = read_env_file(&path).unwrap_err();
+        let _ = fs::remove_file(&path);
+        assert!(e.to_string().ends_with(":3: expect KEY=VALUE"), "{e}");
+    }
+
+    #[test]
+    fn add_params() {
+        let (app, no_start, force) = parse_add_params(&strings(&[
+            "--cwd", "/srv", "--env", "A=b=c", "--no-start", "prod/web", "node", "--inspect", "x",
+        ]))
+        .unwrap();
+        assert_eq!((no_start, force), (true, false));
+        assert_eq!(app.name, "prod/web");
+        assert_eq!(app.namespace.as_deref(), Some("prod"));
+        assert_eq!(app.cwd, PathBuf::from("/srv"));
+        assert_eq!(app.env.get("A").map(String::as_str), Some("b=c"));
+        assert_eq!(app.cmd, "node");
+        /* flags after <cmd> belong to the app */
+        assert_eq!(app.args, strings(&["--inspect", "x"]));
+
+        /* after `--` even <name> and <cmd> may look like flags */
+        let (app, _, force) =
+            parse_add_params(&strings(&["--force", "--", "--web", "--cmd", "--", "-v"])).unwrap();
+        assert!(force);
+        assert_eq!((app.name.as_str(), app.cmd.as_str()), ("--web", "--cmd"));
+        assert_eq!(app.args, strings(&["--", "-v"]));
+
+        /* flags between <name> and <cmd> are still flags */
+        let (app, _, _) =
+            parse_add_params(&strings(&["web", "--restart", "never", "--kill-signal", "int", "--", "run"]))
+                .unwrap();
+        assert_eq!(app.restart, Some(RestartPolicy::Never));
+        /* stored canonical, so stop sends SIGINT and not the SIGTERM fallback */
+        assert_eq!(app.kill_signal.as_deref(), Some("SIGINT"));
+        assert_eq!(app.kill_signal(), nix::sys::signal::Signal::SIGINT);
+        assert_eq!(app.cmd, "run");
+        assert!(app.args.is_empty());
+
+        for params in [
+            &["web"][..],
+            &["web", "--"],
+            &["--cwd"],
+            &["--bogus", "web", "run"],
+            &["--env", "NOEQUALS", "web", "run"],
+            &["--kill-signal", "SIGFOO", "web", "run"],
+            &["a/b/c", "run"],
+            &["a.b", "run"],
+            &["a/b.c", "run"],
+        ] {
+            assert!(parse_add_params(&strings(params)).is_err(), "{params:?}");
+        }
+    }
+
+    #[test]
+    fn app_config_validate() {
+        let app = |snippet: &str| {
+            let toml = format!("name = \"web\"\ncmd = \"run\"\nargs = []\ncwd = \"/\"\nenabled = true\n{snippet}");
+            toml::from_str::<AppConfig>(&toml).unwrap()
+        };
+        assert_eq!(app("kill_signal = \"int\"").validate(), Ok(()));
+        assert_eq!(app("listen = [{ tcp = \"127.0.0.1:0\" }, { unix = \"/tmp/x\" }]").validate(), Ok(()));
+        for snippet in [
+            "namespace = \"a.b\"",
+            "kill_signal = \"SIGFOO\"",
+            "ionice_class = \"fast\"",
+            "ionice_level = 9",
+            "cpu_affinity = \"3-1\"",
+            "listen = [{ name = \"none\" }]",
+            "listen = [{ tcp = \"127.0.0.1:0\", unix = \"/tmp/x\" }]",
+        ] {
+            assert!(app(snippet).validate().is_err(), "{snippet}");
+        }
+    }
+
+    #[test]
+    fn command_spec_validate() {
+        let add = CommandSpec::find("add").unwrap();
+        assert_eq!(add.validate(&strings(&["web", "/bin/app", "--verbose", "-x"])), Ok(()));
+        assert_eq!(add.validate(&strings(&["--cwd", "/srv", "web", "/bin/app"])), Ok(()));
+        assert_eq!(add.validate(&strings(&["--", "--web", "--app"])), Ok(()));
+        assert_eq!(add.validate(&strings(&["web", "--", "--app"])), Ok(()));
+        assert_eq!(
+            add.validate(&strings(&["web"])),
+            Err("missing </path/to/app>".to_string())
+        );
+        assert_eq!(
+            add.validate(&strings(&["web", "--"])),
+            Err("missing </path/to/app>".to_string())
+        );
+        assert_eq!(
+            add.validate(&strings(&["web", "--cwd"])),
+            Err("`--cwd` needs a value".to_string())
+        );
+        assert_eq!(
+            add.validate(&strings(&["--bogus", "web", "/bin/app"])),
+            Err("unknown flag `--bogus`".to_string())
+        );
+
+        let stop = CommandSpec::find("stop").unwrap();
+        assert_eq!(stop.validate(&strings(&["web"])), Ok(()));
+        assert_eq!(stop.validate(&[]), Err("missing <apps>".to_string()));
+        assert_eq!(stop.validate(&strings(&["a", "b"])), Err("too many arguments".to_string()));
+
+        /* aliases find the same spec */
+        let ls = CommandSpec::find("list").unwrap();
+        assert_eq!(ls.name, "ls");
+        assert_eq!(ls.validate(&[]), Ok(()));
+        assert_eq!(ls.validate(&strings(&["--group-by", "tag"])), Ok(()));
+    }
+}